@@ -1,4 +1,4 @@
-use std::io;
+use std::{env, io};
 
 #[cfg(windows)]
 extern crate windres;
@@ -8,5 +8,15 @@ use windres::Build;
 fn main() -> io::Result<()> {
     #[cfg(windows)]
     Build::new().compile("resources.rc").unwrap();
+
+    //Link against unarr, optionally from a custom location
+    if env::var_os("CARGO_FEATURE_UNARR").is_some() {
+        println!("cargo:rerun-if-env-changed=UNARR_LIB_DIR");
+        if let Ok(lib_dir) = env::var("UNARR_LIB_DIR") {
+            println!("cargo:rustc-link-search=native={lib_dir}");
+        }
+        println!("cargo:rustc-link-lib=unarr");
+    }
+
     Ok(())
 }
@@ -1,9 +1,31 @@
 use std::{cmp::min, collections::HashMap, ffi::CString};
 
-use crate::{chunkprovider::metaprovider::MetaProvider, database::Database};
+use crate::{
+    chunkprovider::metaprovider::MetaProvider,
+    database::{title_from_path, Database},
+    paths::AppPaths,
+    processing::make_thumbnail,
+    scanner::LibraryScanner,
+};
 use raylib::prelude::*;
 
+mod bookmarks;
+mod continuous;
+mod library;
+mod pageview;
+mod statistics;
+mod tags;
+mod zoom;
+
+use bookmarks::Bookmarks;
+use library::Library;
+use statistics::SessionTracker;
+use tags::TagEditor;
+use zoom::{wheel_move, Zoom};
+
 const DOTS_SHOW_TIMEOUT: f32 = 1.5;
+//Textures kept loaded, enough for the current and previous spread
+const MAX_LOADED_TEXTURES: usize = 4;
 const MAX_RECENT_DOCUMENTS: usize = 8;
 
 const CARD_WIDTH: usize = 120;
@@ -11,7 +33,10 @@ const CARD_HEIGHT: usize = CARD_WIDTH * 16 / 9;
 const CARD_SPACING: usize = 20;
 
 use crate::{
-    structs::{Chunk, ComicMetadata},
+    structs::{
+        Chunk, ChunkCache, ComicMetadata, DetectionParams, LibraryFilter, PageFit, PageOrder,
+        ReadingDirection, ReadingStatistics, ViewMode,
+    },
     traits::IChunkProvider,
 };
 
@@ -24,6 +49,20 @@ pub enum CardAction {
     None,
     OpenDocument,
     RemoveDocument,
+    ToggleFavourite,
+    //Edit the document's tags and collections
+    EditTags,
+}
+
+//Screens shown while no document is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Screen {
+    //Recent documents
+    Lobby,
+    //Every document, searchable
+    Library,
+    //Time read and completion
+    Statistics,
 }
 
 #[allow(dead_code)]
@@ -101,19 +140,77 @@ pub struct Application {
     pub db: Database,
     //Current Document Path
     pub current_document_path: Option<String>,
+    //Thumbnail of the open document, made from its first page when it had none
+    current_thumbnail: Option<Vec<u8>>,
     pub logo_texture: Texture2D,
     pub recent_thumbs: Vec<Texture2D>,
     pub recent_thumbs_data: Vec<Vec<u8>>,
     show_dots_timeout: f32,
     title_changed: bool,
     can_scroll: bool,
+    //How the current document's panels are ordered
+    reading_direction: ReadingDirection,
+    //Page to jump to once its chunks are available
+    target_page: Option<usize>,
+    //Position on the target page to land on, the chunk overlapping it the most
+    target_rect: Option<Rectangle>,
+    //Detector tunables the current document's chunks are computed with
+    detection_params: DetectionParams,
+    //How the current document's pages are sorted
+    page_order: PageOrder,
+    //Screen shown while no document is open
+    screen: Screen,
+    library: Library,
+    //Library scan running in the background
+    scanner: Option<LibraryScanner>,
+    //Volume offered once the reader moves past the end of the current one
+    next_volume: Option<ComicMetadata>,
+    bookmarks: Bookmarks,
+    //Reading session of the open document
+    session: Option<SessionTracker>,
+    //Shown by the statistics screen, None to compute them again
+    statistics: Option<ReadingStatistics>,
+    statistics_scroll: Vector2,
+    //Tags and collections of a document being edited from its card
+    tag_editor: Option<TagEditor>,
+    //Documents shown by the lobby, recent ones or favourites
+    lobby_filter: LibraryFilter,
+    //Whether the current document is read by chunks or by whole pages
+    view_mode: ViewMode,
+    //How whole pages are scaled in page mode
+    page_fit: PageFit,
+    //Page shown in page mode, None until it's known from the current chunk
+    current_page: Option<usize>,
+    //Page offset in page mode, 0 or negative on both axes
+    page_scroll: Vector2,
+    //Chunk rect to scroll to once the page's size is known
+    page_focus: Option<Rectangle>,
+    //Part of the page on screen, to land on the matching chunk when going back to chunk mode
+    page_view_rect: Option<Rectangle>,
+    //Whether the first page is shown alone in spreads
+    spread_shift: bool,
+    //Image sizes of the pages loaded so far, to tell apart double spreads
+    page_sizes: HashMap<usize, Vector2>,
+    //How far down current_page is scrolled in continuous mode, as a fraction of its height
+    strip_offset: f32,
+    //Scrolling left to do in continuous mode, spread over the next frames
+    strip_pending_scroll: f32,
+    //Pages drawn on the last frame, their textures are never evicted
+    shown_textures: Vec<usize>,
+    //Zoom and pan over the chunk or page, along with the loupe
+    zoom: Zoom,
 }
 
 impl Application {
     /// Creates a new [`Application`].
-    pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread, logo_texture: Texture2D) -> Self {
+    pub fn new(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        logo_texture: Texture2D,
+        paths: &AppPaths,
+    ) -> Self {
         let provider = Box::new(MetaProvider::new());
-        let db = Database::new();
+        let db = Database::new(&paths.database());
 
         //Return a new application
         let mut app = Self {
@@ -130,16 +227,47 @@ impl Application {
             fonts: ApplicationFonts::new(rl, thread),
             db,
             current_document_path: None,
+            current_thumbnail: None,
             logo_texture,
             recent_thumbs: Vec::new(),
             recent_thumbs_data: Vec::new(),
             show_dots_timeout: 5.0,
             title_changed: false,
             can_scroll: true,
+            reading_direction: ReadingDirection::default(),
+            target_page: None,
+            target_rect: None,
+            detection_params: DetectionParams::default(),
+            page_order: PageOrder::default(),
+            screen: Screen::Lobby,
+            library: Library::new(),
+            scanner: None,
+            next_volume: None,
+            bookmarks: Bookmarks::new(),
+            session: None,
+            statistics: None,
+            statistics_scroll: Vector2::zero(),
+            tag_editor: None,
+            lobby_filter: LibraryFilter::All,
+            view_mode: ViewMode::default(),
+            page_fit: PageFit::default(),
+            current_page: None,
+            page_scroll: Vector2::zero(),
+            page_focus: None,
+            page_view_rect: None,
+            spread_shift: false,
+            page_sizes: HashMap::new(),
+            strip_offset: 0.0,
+            strip_pending_scroll: 0.0,
+            shown_textures: Vec::new(),
+            zoom: Zoom::new(),
         };
 
         app.update_recents();
 
+        //Pick up whatever changed in the library folders since last run
+        app.start_library_scan();
+
         app
     }
 
@@ -184,13 +312,24 @@ impl Application {
             self.recent_thumbs_data.clear();
         }
 
-        //Check for texture queries
+        self.library.load_thumbnails(context, thread);
+
+        //Check for texture queries, several pages may be shown at once
         for query in self.image_queries.iter() {
+            if self.textures.contains_key(query) {
+                continue;
+            }
+
             eprintln!("Loading texture {:?}", query);
 
             let provider = &mut self.provider;
             //Try to get the image from the provider
             if let Some(image) = provider.get_image(*query) {
+                self.page_sizes.insert(
+                    *query,
+                    Vector2::new(image.width() as f32, image.height() as f32),
+                );
+
                 //Get the texture from the image
                 let value = match context.load_texture_from_image(thread, image) {
                     Ok(it) => Some(it),
@@ -203,24 +342,35 @@ impl Application {
                 self.textures.insert(*query, value);
 
                 //Store first page as thumbnail
-                if *query == 0 && self.recent_documents[0].thumbnail.is_none() {
-                    const TEMP_FILENAME: &str = ".manga_viewer_thumb.jpg";
-                    let mut img = image.clone();
-                    img.resize(CARD_WIDTH as i32, CARD_HEIGHT as i32);
-                    img.export_image(TEMP_FILENAME);
-                    if let Ok(data) = std::fs::read(TEMP_FILENAME) {
-                        self.recent_documents[0].thumbnail = Some(data);
-                    }
+                if *query == 0 && self.current_thumbnail.is_none() {
+                    if let Some(path) = &self.current_document_path {
+                        self.current_thumbnail =
+                            make_thumbnail(image, CARD_WIDTH as i32, CARD_HEIGHT as i32);
 
-                    std::fs::remove_file(TEMP_FILENAME);
-                }
-
-                if self.textures.len() >= 4 && !self.texture_loading_order.is_empty() {
-                    self.textures
-                        .remove(&self.texture_loading_order.pop().unwrap());
+                        if let Some(thumbnail) = &self.current_thumbnail {
+                            self.db.set_thumbnail(path, thumbnail);
+                        }
+                    }
                 }
 
                 self.texture_loading_order.insert(0, *query);
+
+                //Drop the oldest ones first, keeping those on screen
+                let limit = MAX_LOADED_TEXTURES.max(self.shown_textures.len() + 2);
+                while self.textures.len() > limit {
+                    let oldest = self
+                        .texture_loading_order
+                        .iter()
+                        .rposition(|it| !self.shown_textures.contains(it));
+
+                    match oldest {
+                        Some(position) => {
+                            let index = self.texture_loading_order.remove(position);
+                            self.textures.remove(&index);
+                        }
+                        None => break,
+                    }
+                }
             }
         }
     }
@@ -229,6 +379,8 @@ impl Application {
     //Draw Application
     pub fn draw(&mut self, screen_rect: Rectangle, context: &mut RaylibDrawHandle) {
         context.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_ARROW);
+        self.poll_library_scan();
+
         if self.show_dots_timeout > 0.0 {
             self.show_dots_timeout -= 1.0 / (context.get_fps() as f32);
         }
@@ -273,14 +425,31 @@ impl Application {
             return;
         }
 
-        //Handle user input
-        self.handle_input(context, &screen_rect);
+        self.shown_textures.clear();
+
+        if self.view_mode == ViewMode::Continuous {
+            self.draw_continuous(screen_rect, context);
+            return;
+        }
+
+        if self.view_mode != ViewMode::Chunk {
+            self.draw_page(screen_rect, context);
+            return;
+        }
+
+        //Handle user input, unless it's being typed into a note
+        if !self.bookmarks.is_editing() {
+            self.handle_input(context, &screen_rect);
+        }
 
         self.smoothed_scroll += (self.scroll - self.smoothed_scroll) * 0.5;
 
         //Draw borders(this is intended for debugging only)
         // context.draw_rectangle_lines_ex(screen_rect, 1, Color::DARKGRAY);
 
+        //Wait for the target page to be segmented before showing anything else
+        self.resolve_target_page();
+
         //Unwrap a reference to the provider
         let provider = &mut self.provider;
         //Store current chunk in cache
@@ -293,6 +462,10 @@ impl Application {
             None
         };
 
+        if let (Some(session), Some(chunk)) = (self.session.as_mut(), &self.current_chunk) {
+            session.record_page(chunk.texture_index);
+        }
+
         //If a chunk has been retrieved from the provider
         if let Some(chunk) = &self.current_chunk {
             //Check if there is a texture already loaded from the provider
@@ -312,28 +485,41 @@ impl Application {
 
             //If the texture exists in cache
             if let Some(t) = texture {
-                //Width/Height Ratio
-                let coeff = chunk.rect.height / chunk.rect.width;
+                //Size of the chunk once zoomed into the screen
+                let chunk_real_size = chunk_screen_size(chunk, &screen_rect);
 
                 //Does the chunk fits in the screen?
-                let chunk_real_height = screen_rect.width * coeff;
-                let chunk_fits = chunk_real_height <= screen_rect.height;
+                let chunk_fits = chunk_real_size.y <= screen_rect.height;
 
                 //Calculate the target rectangle for the texture
                 let target_rect = Rectangle::new(
-                    screen_rect.x,
+                    //Narrow chunks are centered horizontally
+                    screen_rect.x + (screen_rect.width - chunk_real_size.x) / 2.0,
                     if chunk_fits {
                         //If fits center the chunk vertically
-                        (screen_rect.height - chunk_real_height) / 2.0
+                        (screen_rect.height - chunk_real_size.y) / 2.0
                     } else {
                         //Else start at screen_rect's beginning
                         0.0
                     } + screen_rect.y
                         + self.smoothed_scroll,
-                    screen_rect.width,
-                    screen_rect.width * coeff,
+                    chunk_real_size.x,
+                    chunk_real_size.y,
                 );
 
+                self.zoom.show(ViewMode::Chunk, self.current_chunk_index);
+                let target_rect = self.zoom.fit(target_rect, chunk.rect.width, &screen_rect);
+
+                //Zoomed chunks may be larger than the screen
+                unsafe {
+                    raylib::ffi::BeginScissorMode(
+                        screen_rect.x as i32,
+                        screen_rect.y as i32,
+                        screen_rect.width as i32,
+                        screen_rect.height as i32,
+                    );
+                }
+
                 //Draw the texture
                 context.draw_texture_pro(
                     t,
@@ -342,7 +528,16 @@ impl Application {
                     Vector2::zero(),
                     0f32,
                     Color::WHITE,
-                )
+                );
+
+                self.zoom.draw_loupe(context, t, chunk.rect, target_rect);
+
+                unsafe {
+                    raylib::ffi::EndScissorMode();
+                }
+
+                self.zoom
+                    .draw_level(context, &screen_rect, self.fonts.default());
             } else {
                 //Draw a label with a "No Texture" message
                 draw_text_centered(
@@ -355,6 +550,12 @@ impl Application {
             }
         };
 
+        if self.draw_next_volume(&screen_rect, context) {
+            return;
+        }
+
+        self.draw_bookmarks(&screen_rect, context);
+
         //Y position for the indicator
         let mut y = screen_rect.y - 12.0 + screen_rect.height;
 
@@ -444,6 +645,8 @@ impl Application {
         let mut real_size: Vector2 = Vector2::new(0.0, 0.0);
         let mut chunk_index_offset: i32 = 0;
 
+        self.zoom.update(context, screen_size);
+
         if context.is_key_released(KeyboardKey::KEY_UP)
             || context.is_key_released(KeyboardKey::KEY_DOWN)
         {
@@ -453,10 +656,8 @@ impl Application {
         //Handle user scroll only if there is a chunk
         if let Some(chunk) = &self.current_chunk {
             if self.can_scroll {
-                //Calculate width/height ratio
-                let coeff = chunk.rect.height / chunk.rect.width;
                 //Calculate the chunk's screen-size
-                real_size = Vector2::new(screen_size.width, screen_size.width * coeff);
+                real_size = chunk_screen_size(chunk, screen_size);
 
                 //If the chunk is taller than the screen then enable vertical scroll
                 if real_size.y > screen_size.height {
@@ -468,7 +669,7 @@ impl Application {
                         screen_size.height * 0.1
                     } else {
                         //If no keys were detected then try to get mousewheel's value
-                        context.get_mouse_wheel_move() * 0.1 * (context.get_screen_height() as f32)
+                        wheel_move(context) * 0.1 * (context.get_screen_height() as f32)
                     };
 
                     //Max possible offset
@@ -512,9 +713,65 @@ impl Application {
         //Initial chunk index
         let initial_chunk_index = self.current_chunk_index;
 
+        if self.handle_mode_keys(context) {
+            return;
+        }
+
+        //Brackets split pages less or more eagerly, for this document only
+        if context.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+            self.adjust_detection_params(|params| {
+                params.gutter_strip_threshold =
+                    params.gutter_strip_threshold.saturating_sub(1).max(1)
+            });
+            return;
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+            self.adjust_detection_params(|params| params.gutter_strip_threshold += 1);
+            return;
+        }
+
+        //G makes this document's parameters the default, Shift+G goes back to the default
+        if context.is_key_pressed(KeyboardKey::KEY_G) {
+            if context.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                || context.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT)
+            {
+                self.reset_detection_params();
+            } else {
+                self.make_detection_params_global();
+            }
+            return;
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_B) {
+            self.toggle_bookmark();
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_N) {
+            self.edit_bookmark_note();
+            return;
+        }
+
+        //Right to left documents advance towards the left
+        let right_to_left = self.reading_direction == ReadingDirection::RightToLeft;
+        let (next_key, previous_key) = if right_to_left {
+            (KeyboardKey::KEY_LEFT, KeyboardKey::KEY_RIGHT)
+        } else {
+            (KeyboardKey::KEY_RIGHT, KeyboardKey::KEY_LEFT)
+        };
+
+        //Clicks on the next volume prompt and bookmarks are handled by their buttons,
+        //a click is only known once it can't be a double click anymore
+        let click = self.zoom.take_click().filter(|point| {
+            !((self.next_volume.is_some()
+                && next_volume_rect(screen_size).check_collision_point_rec(*point))
+                || self.bookmarks_hovered(screen_size, *point))
+        });
+
         let click_gesture = {
-            if context.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
-                if context.get_mouse_x() < ((screen_size.width as i32) / 2) {
+            if let Some(point) = click {
+                let left_half = (point.x as i32) < ((screen_size.width as i32) / 2);
+                if left_half != right_to_left {
                     0b01
                 } else {
                     0b10
@@ -526,7 +783,7 @@ impl Application {
 
         //Check for simple next/prev events
         if context.is_key_pressed(KeyboardKey::KEY_PAGE_DOWN)
-            || context.is_key_pressed(KeyboardKey::KEY_RIGHT)
+            || context.is_key_pressed(next_key)
             || (click_gesture == 0b10)
         {
             chunk_index_offset = 1;
@@ -539,7 +796,7 @@ impl Application {
                 self.scroll = 0.0;
             }
         } else if context.is_key_pressed(KeyboardKey::KEY_PAGE_UP)
-            || context.is_key_pressed(KeyboardKey::KEY_LEFT)
+            || context.is_key_pressed(previous_key)
             || (click_gesture == 0b01)
         {
             chunk_index_offset = -1;
@@ -562,8 +819,24 @@ impl Application {
 
                 if self.current_chunk_index >= provider.chunk_count() {
                     self.current_chunk_index = provider.chunk_count() - 1;
+
+                    //Moving past the end of a finished volume
+                    if provider.done_processing() && chunk_index_offset > 0 {
+                        self.next_volume = self
+                            .current_document_path
+                            .as_ref()
+                            .and_then(|path| self.db.next_volume(path));
+                    }
                 }
             }
+
+            if chunk_index_offset < 0 {
+                self.next_volume = None;
+            }
+
+            if let Some(session) = self.session.as_mut() {
+                session.record_move(initial_chunk_index, self.current_chunk_index);
+            }
         }
 
         if self.current_chunk_index > initial_chunk_index {
@@ -573,12 +846,67 @@ impl Application {
         }
     }
 
+    //Prompt to continue with the next volume, returns true if it was opened
+    fn draw_next_volume(
+        &mut self,
+        screen_rect: &Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) -> bool {
+        let title = match &self.next_volume {
+            Some(it) => format!("Next volume: {}", it.title),
+            None => return false,
+        };
+
+        let rect = next_volume_rect(screen_rect);
+        let button_width = (rect.width - 30.0) / 2.0;
+
+        context.draw_rectangle_rec(rect, Color::WHITE.fade(0.9));
+        context.draw_rectangle_lines_ex(rect, 1, Color::DARKGRAY);
+        draw_text_centered(
+            context,
+            title.as_str(),
+            Rectangle::new(rect.x, rect.y + 5.0, rect.width, 24.0),
+            self.fonts.default(),
+            Color::BLACK,
+        );
+
+        let open = context.gui_button(
+            Rectangle::new(rect.x + 10.0, rect.y + 34.0, button_width, 24.0),
+            Some(CString::new("Open").unwrap().as_c_str()),
+        ) || context.is_key_pressed(KeyboardKey::KEY_ENTER);
+
+        let dismiss = context.gui_button(
+            Rectangle::new(
+                rect.x + 20.0 + button_width,
+                rect.y + 34.0,
+                button_width,
+                24.0,
+            ),
+            Some(CString::new("Stay").unwrap().as_c_str()),
+        ) || context.is_key_pressed(KeyboardKey::KEY_ESCAPE);
+
+        if dismiss {
+            self.next_volume = None;
+        } else if open {
+            if let Some(next) = self.next_volume.take() {
+                if let Err(error) = self.open_document(&next.path) {
+                    log::error!("Error opening next volume: {error}");
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+
+    //Cards of documents that can be tagged get a favourite star and a tags button
     fn draw_recent_card(
         &self,
         rect: Rectangle,
         context: &mut RaylibDrawHandle,
         metadata: Option<&ComicMetadata>,
         thumbnail: Option<&Texture2D>,
+        editable: bool,
     ) -> CardAction {
         if metadata.is_none() {
             context.draw_rectangle_lines_ex(
@@ -685,6 +1013,45 @@ impl Application {
         //     return CardAction::RemoveDocument;
         // }
 
+        if editable {
+            let favourite = metadata.is_some_and(|it| it.favourite);
+            let star_center = Vector2::new(rect.x + 14.0, rect.y + 14.0);
+            let star_hovered = hovered
+                && check_collision_point_circle(context.get_mouse_position(), star_center, 12.0);
+
+            if favourite || hovered {
+                draw_star(
+                    context,
+                    star_center,
+                    10.0,
+                    if favourite {
+                        Color::GOLD
+                    } else if star_hovered {
+                        Color::GOLD.fade(0.6)
+                    } else {
+                        Color::LIGHTGRAY.fade(0.8)
+                    },
+                );
+            }
+
+            if star_hovered && context.is_mouse_button_released(MouseButton::MOUSE_LEFT_BUTTON) {
+                return CardAction::ToggleFavourite;
+            }
+
+            if hovered
+                && context.gui_button(
+                    Rectangle::new(rect.x + rect.width - 44.0, rect.y + 4.0, 40.0, 18.0),
+                    Some(CString::new("Tags").unwrap().as_c_str()),
+                )
+            {
+                return CardAction::EditTags;
+            }
+
+            if hovered && context.is_mouse_button_released(MouseButton::MOUSE_RIGHT_BUTTON) {
+                return CardAction::EditTags;
+            }
+        }
+
         if context.is_mouse_button_released(MouseButton::MOUSE_LEFT_BUTTON) && hovered {
             return CardAction::OpenDocument;
         }
@@ -696,10 +1063,71 @@ impl Application {
             return false;
         }
 
+        if self.draw_tag_editor(screen_rect, context) {
+            return true;
+        }
+
+        if self.screen == Screen::Library {
+            return self.library(screen_rect, context);
+        }
+
+        if self.screen == Screen::Statistics {
+            return self.statistics(screen_rect, context);
+        }
+
+        if context.gui_button(
+            Rectangle::new(
+                screen_rect.x + screen_rect.width - 170.0,
+                screen_rect.y,
+                80.0,
+                24.0,
+            ),
+            Some(CString::new("Statistics").unwrap().as_c_str()),
+        ) {
+            self.screen = Screen::Statistics;
+            return true;
+        }
+
+        let filter = context.gui_toggle_group(
+            Rectangle::new(screen_rect.x, screen_rect.y, 90.0, 24.0),
+            Some(CString::new("Recent;Favourites").unwrap().as_c_str()),
+            (self.lobby_filter == LibraryFilter::Favourites) as i32,
+        );
+        let filter = if filter == 1 {
+            LibraryFilter::Favourites
+        } else {
+            LibraryFilter::All
+        };
+
+        if filter != self.lobby_filter {
+            self.lobby_filter = filter;
+            self.update_recents();
+            return true;
+        }
+
+        let favourites = self.lobby_filter == LibraryFilter::Favourites;
+
+        if context.gui_button(
+            Rectangle::new(
+                screen_rect.x + screen_rect.width - 80.0,
+                screen_rect.y,
+                80.0,
+                24.0,
+            ),
+            Some(CString::new("Library").unwrap().as_c_str()),
+        ) {
+            self.screen = Screen::Library;
+            return true;
+        }
+
         if self.recent_documents.len() == 0 {
             draw_text_centered(
                 context,
-                "No Recent documents",
+                if favourites {
+                    "No favourite documents"
+                } else {
+                    "No Recent documents"
+                },
                 screen_rect,
                 &self.fonts.large(),
                 Color::BLACK,
@@ -707,7 +1135,11 @@ impl Application {
         } else {
             draw_text_centered(
                 context,
-                "Recent documents:",
+                if favourites {
+                    "Favourite documents:"
+                } else {
+                    "Recent documents:"
+                },
                 Rectangle::new(
                     screen_rect.x,
                     screen_rect.y + 20f32,
@@ -753,7 +1185,13 @@ impl Application {
                                 self.recent_thumbs.get(index)
                             };
 
-                            match self.draw_recent_card(rect, context, Some(metadata), thumbnail) {
+                            match self.draw_recent_card(
+                                rect,
+                                context,
+                                Some(metadata),
+                                thumbnail,
+                                true,
+                            ) {
                                 CardAction::None => {}
                                 CardAction::OpenDocument => {
                                     if let Some(metadata) = self.recent_documents.get(index) {
@@ -765,10 +1203,23 @@ impl Application {
                                     self.recent_documents.remove(index);
                                     return true;
                                 }
+                                CardAction::ToggleFavourite => {
+                                    let (path, favourite) =
+                                        (metadata.path.clone(), metadata.favourite);
+                                    self.db.set_favourite(&path, !favourite);
+                                    self.update_recents();
+                                    return true;
+                                }
+                                CardAction::EditTags => {
+                                    let (path, title) =
+                                        (metadata.path.clone(), metadata.title.clone());
+                                    self.open_tag_editor(&path, &title);
+                                    return true;
+                                }
                             }
                         }
                     } else {
-                        self.draw_recent_card(rect, context, None, None);
+                        self.draw_recent_card(rect, context, None, None, false);
                     }
                 }
             }
@@ -777,6 +1228,40 @@ impl Application {
         return true;
     }
 
+    //Scan the library roots in the background, unless a scan is already running
+    fn start_library_scan(&mut self) {
+        if self.scanner.is_some() {
+            return;
+        }
+
+        let roots = self.db.library_roots();
+        if roots.is_empty() {
+            return;
+        }
+
+        self.scanner = Some(LibraryScanner::spawn(
+            roots,
+            self.db.paths_with_thumbnail(),
+            (CARD_WIDTH as i32, CARD_HEIGHT as i32),
+        ));
+    }
+
+    //Store the results of the library scan once it's over
+    fn poll_library_scan(&mut self) {
+        let finished = match self.scanner.as_mut() {
+            Some(scanner) => scanner.poll(),
+            None => return,
+        };
+
+        if finished {
+            if let Some(scanner) = self.scanner.take() {
+                let (roots, found) = scanner.into_results();
+                self.db.sync_library(&roots, found);
+                self.update_recents();
+            }
+        }
+    }
+
     pub fn add_error(&mut self, title: &str, message: &str, callback: Option<fn()>) {
         self.errors
             .push((String::from(title), String::from(message), callback));
@@ -787,27 +1272,88 @@ impl Application {
 
         self.close_document();
 
-        let cached_chunks = self.db.chunks_for(path);
+        let stored_metadata = self.db.metadata_for(path);
 
-        match self.provider.open(path.as_str(), Some(cached_chunks)) {
+        //Documents without their own detection params use the global ones
+        self.detection_params = stored_metadata
+            .as_ref()
+            .and_then(|it| it.detection_params)
+            .unwrap_or_else(|| self.db.global_detection_params());
+        self.provider.set_detection_params(self.detection_params);
+
+        let cache = ChunkCache {
+            chunks: self.db.chunks_for(path, &self.detection_params),
+            pages: self.db.pages_for(path),
+        };
+
+        //Chunks are ordered according to the document's reading direction
+        self.reading_direction = stored_metadata
+            .as_ref()
+            .map_or(ReadingDirection::default(), |it| it.reading_direction);
+        self.provider.set_reading_direction(self.reading_direction);
+
+        self.page_order = stored_metadata
+            .as_ref()
+            .map_or(PageOrder::default(), |it| it.page_order);
+        self.provider.set_page_order(self.page_order);
+
+        //Pages the reader turned, before they are decoded or segmented
+        self.provider
+            .set_page_rotations(self.db.page_rotations_for(path));
+
+        self.view_mode = stored_metadata
+            .as_ref()
+            .map_or(ViewMode::default(), |it| it.view_mode);
+        self.page_fit = stored_metadata
+            .as_ref()
+            .map_or(PageFit::default(), |it| it.page_fit);
+        self.spread_shift = stored_metadata.as_ref().is_some_and(|it| it.spread_shift);
+
+        match self.provider.open(path.as_str(), Some(cache)) {
             Err(error) => {
                 self.add_error("Error", error.as_str(), None);
 
                 return Err("Couldn't find a situable provider".to_string());
             }
             Ok(_) => {
-                let mut metadata = if let Some(md) = self.db.metadata_for(path) {
+                let fingerprint = self.provider.chunk_cache().map(|it| it.fingerprint());
+
+                //A moved document is opened again, with the progress and chunks it had before
+                if let Some(fingerprint) = &fingerprint {
+                    if self.db.relink_document(path, fingerprint) {
+                        self.provider.unload();
+                        return self.open_document(path);
+                    }
+                }
+
+                //Forget cached chunks of pages that changed since last time
+                if let Some(cache) = self.provider.chunk_cache() {
+                    self.db
+                        .save_chunk_cache(path.clone(), &cache, &self.detection_params);
+                }
+
+                let mut metadata = if let Some(md) = stored_metadata {
                     md
                 } else {
                     eprintln!("Using default metadata for {path}!");
 
                     let new_metadata = ComicMetadata {
                         last_time_opened: get_time() as u64,
-                        title: String::from(path),
+                        title: title_from_path(path),
                         chunk_count: 0,
+                        page_count: 0,
                         last_seen_chunk: 0,
                         path: String::from(path),
                         thumbnail: None,
+                        reading_direction: self.reading_direction,
+                        detection_params: None,
+                        page_order: self.page_order,
+                        favourite: false,
+                        view_mode: self.view_mode,
+                        page_fit: self.page_fit,
+                        spread_shift: self.spread_shift,
+                        last_seen_page: 0,
+                        page_offset: 0.0,
                     };
 
                     //Save metadata for this document
@@ -820,6 +1366,13 @@ impl Application {
 
                 metadata.last_time_opened = get_time();
                 self.current_chunk_index = metadata.last_seen_chunk;
+
+                //Page based modes start on the page they were left on, without waiting for chunks
+                if self.view_mode != ViewMode::Chunk {
+                    let last_page = self.provider.page_count().saturating_sub(1);
+                    self.current_page = Some(metadata.last_seen_page.min(last_page));
+                    self.strip_offset = metadata.page_offset;
+                }
                 self.recent_documents.push(metadata.clone());
 
                 if let Err(error) = self
@@ -829,6 +1382,13 @@ impl Application {
                     return Err(error.to_string());
                 }
 
+                if let Some(fingerprint) = &fingerprint {
+                    self.db.set_fingerprint(&metadata.path, fingerprint);
+                }
+
+                self.bookmarks.set(self.db.bookmarks_for(&metadata.path));
+                self.session = Some(SessionTracker::new(&metadata.path));
+                self.current_thumbnail = metadata.thumbnail.clone();
                 self.current_document_path = Some(metadata.path);
 
                 self.update_recents();
@@ -839,7 +1399,8 @@ impl Application {
     }
 
     fn update_recents(&mut self) {
-        self.recent_documents = self.db.get_recents();
+        self.recent_documents = self.db.get_recents(&self.lobby_filter);
+        self.library.invalidate();
         self.recent_thumbs.clear();
         self.recent_thumbs_data.clear();
 
@@ -860,37 +1421,97 @@ impl Application {
             last_time_opened: 0,
             title: String::new(),
             chunk_count: 0,
+            page_count: 0,
             last_seen_chunk: 0,
             path: String::new(),
             thumbnail: None,
+            reading_direction: ReadingDirection::default(),
+            detection_params: None,
+            page_order: PageOrder::default(),
+            favourite: false,
+            view_mode: ViewMode::default(),
+            page_fit: PageFit::default(),
+            spread_shift: false,
+            last_seen_page: 0,
+            page_offset: 0.0,
         };
 
-        let current_metadata = match self.recent_documents.first() {
+        //Recent documents may be filtered, the open one isn't always first
+        let stored_metadata = self.db.metadata_for(&path);
+        let current_metadata = match &stored_metadata {
             Some(it) => it,
             None => &default_comic_metadata,
         };
+
+        //Page modes don't ask for chunks every frame, collect what the worker segmented meanwhile
+        self.provider.get_chunk(self.current_chunk_index);
+
         let metadata = ComicMetadata {
-            chunk_count: self.provider.chunk_count(),
+            //Only a fully segmented document knows how many chunks it has
+            chunk_count: if self.provider.done_processing() {
+                self.provider.chunk_count()
+            } else {
+                current_metadata.chunk_count
+            },
+            page_count: self.provider.page_count(),
             last_seen_chunk: self.current_chunk_index,
             last_time_opened: get_time(),
             title: current_metadata.title.to_owned(),
             path,
-            thumbnail: current_metadata.thumbnail.clone(),
+            thumbnail: self
+                .current_thumbnail
+                .take()
+                .or_else(|| current_metadata.thumbnail.clone()),
+            reading_direction: self.reading_direction,
+            detection_params: current_metadata.detection_params,
+            page_order: self.page_order,
+            favourite: current_metadata.favourite,
+            view_mode: self.view_mode,
+            page_fit: self.page_fit,
+            spread_shift: self.spread_shift,
+            last_seen_page: match self.view_mode {
+                ViewMode::Chunk => self.current_chunk.map(|it| it.texture_index),
+                _ => self.current_page,
+            }
+            .unwrap_or(current_metadata.last_seen_page),
+            page_offset: if self.view_mode == ViewMode::Continuous {
+                self.strip_offset
+            } else {
+                0.0
+            },
         };
 
         self.db
             .save_metadata(&Vec::from([&metadata]))
             .expect("Error saving metadata");
 
-        let all_chunks = self.all_chunks();
+        if let Some(cache) = self.provider.chunk_cache() {
+            self.db
+                .save_chunk_cache(metadata.path, &cache, &self.detection_params);
+        }
 
-        self.db.save_chunk_cache(metadata.path, all_chunks);
+        if let Some(session) = self.session.take() {
+            self.db.log_session(&session.finish());
+        }
 
         self.textures.clear();
         self.image_queries.clear();
         self.current_chunk_index = 0;
         self.texture_loading_order.clear();
         self.current_chunk = None;
+        self.target_page = None;
+        self.target_rect = None;
+        self.next_volume = None;
+        self.current_page = None;
+        self.page_scroll = Vector2::zero();
+        self.page_focus = None;
+        self.page_view_rect = None;
+        self.page_sizes.clear();
+        self.strip_offset = 0.0;
+        self.strip_pending_scroll = 0.0;
+        self.shown_textures.clear();
+        self.zoom.reset();
+        self.bookmarks.set(Vec::new());
         self.scroll = 0.0;
         self.smoothed_scroll = 0.0;
         self.provider.unload();
@@ -900,18 +1521,267 @@ impl Application {
         self.title_changed = true;
     }
 
-    fn all_chunks(&mut self) -> Vec<Chunk> {
-        (0..self.provider.chunk_count())
-            .map(|index| match self.provider.get_chunk(index) {
-                Some(it) => *it,
-                None => Chunk {
-                    rect: Rectangle::new(0.0, 0.0, 0.0, 0.0),
-                    texture_index: 0,
-                },
-            })
-            .filter(|x| x.rect.width > 0.0)
-            .collect()
+    //Switch to the next reading direction, segmenting the document again from the current page
+    fn cycle_reading_direction(&mut self) {
+        let direction = self.reading_direction.next();
+
+        //Chunks computed for the previous direction are in the wrong order
+        self.reopen_document(true, |metadata| metadata.reading_direction = direction);
+    }
+
+    //Switch to the next page order, staying on the current page
+    fn cycle_page_order(&mut self) {
+        let order = self.page_order.next();
+
+        //Cached chunks follow their pages by name, they are still valid
+        self.reopen_document(false, |metadata| metadata.page_order = order);
+    }
+
+    //Segment the document again with its own detection parameters, changed from the current ones
+    fn adjust_detection_params(&mut self, update: impl FnOnce(&mut DetectionParams)) {
+        let path = match &self.current_document_path {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        let mut params = self.detection_params;
+        update(&mut params);
+        if params == self.detection_params {
+            return;
+        }
+
+        //Cached chunks are matched by params hash, the ones for the old parameters are ignored
+        self.db.set_detection_params(&path, Some(&params));
+        self.reopen_document(false, |_| {});
     }
+
+    //Drop the document's own detection parameters, segmenting it again with the global ones
+    fn reset_detection_params(&mut self) {
+        let path = match &self.current_document_path {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        let changed = self.detection_params != self.db.global_detection_params();
+        self.db.set_detection_params(&path, None);
+
+        if changed {
+            self.reopen_document(false, |_| {});
+        }
+    }
+
+    //Use the current document's detection parameters for every document without its own
+    fn make_detection_params_global(&mut self) {
+        let path = match &self.current_document_path {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        self.db.set_global_detection_params(&self.detection_params);
+        self.db.set_detection_params(&path, None);
+    }
+
+    //Keys shared by every reading mode: view modes, reading direction, page order, rotation and
+    //the bookmark list, returns whether the key was handled
+    fn handle_mode_keys(&mut self, context: &RaylibDrawHandle) -> bool {
+        let shift = context.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+            || context.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+        if context.is_key_pressed(KeyboardKey::KEY_M) {
+            self.toggle_view_mode();
+        } else if context.is_key_pressed(KeyboardKey::KEY_S) {
+            //Shift+S moves the pairing of spreads by one page
+            if shift {
+                self.spread_shift = !self.spread_shift;
+                self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+            } else {
+                self.toggle_spread();
+            }
+        } else if context.is_key_pressed(KeyboardKey::KEY_C) {
+            self.toggle_continuous();
+        } else if context.is_key_pressed(KeyboardKey::KEY_D) {
+            self.cycle_reading_direction();
+        } else if context.is_key_pressed(KeyboardKey::KEY_P) {
+            self.cycle_page_order();
+        } else if context.is_key_pressed(KeyboardKey::KEY_J) {
+            self.bookmarks.toggle_list();
+        } else {
+            return self.handle_rotation_keys(context);
+        }
+
+        true
+    }
+
+    //R turns the current page clockwise, Shift+R upside down, returns whether the document was reopened
+    fn handle_rotation_keys(&mut self, context: &RaylibDrawHandle) -> bool {
+        if !context.is_key_pressed(KeyboardKey::KEY_R) {
+            return false;
+        }
+
+        let quarter_turns = if context.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+            || context.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT)
+        {
+            2
+        } else {
+            1
+        };
+
+        self.rotate_current_page(quarter_turns);
+        true
+    }
+
+    //Turn the page on screen, its chunks are detected again on the turned image
+    fn rotate_current_page(&mut self, quarter_turns: i64) {
+        let path = match &self.current_document_path {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        let page = match self
+            .provider
+            .chunk_cache()
+            .and_then(|cache| cache.pages.get(self.shown_page()).cloned())
+        {
+            Some(it) => it,
+            None => return,
+        };
+
+        //The new record no longer matches the cached one, so only this page is segmented again
+        self.db
+            .set_page_rotation(&path, &page.name, page.rotation.turned(quarter_turns));
+        self.reopen_document(false, |_| {});
+    }
+
+    //Page the reader is on, in any view mode
+    fn shown_page(&self) -> usize {
+        match (self.view_mode, self.current_page) {
+            (ViewMode::Chunk, _) | (_, None) => self.current_chunk.map_or(0, |it| it.texture_index),
+            (_, Some(page)) => page,
+        }
+    }
+
+    //Close and open the current document again after updating its metadata, keeping the current page
+    fn reopen_document(&mut self, clear_cache: bool, update: impl FnOnce(&mut ComicMetadata)) {
+        let path = match &self.current_document_path {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        //Pages may move around, remember the current one by name
+        let page = self.shown_page();
+        let page_name = self
+            .provider
+            .chunk_cache()
+            .and_then(|cache| cache.pages.get(page).map(|it| it.name.clone()));
+
+        self.close_document();
+
+        if clear_cache {
+            self.db.clear_chunk_cache(&path);
+        }
+
+        if let Some(mut metadata) = self.db.metadata_for(&path) {
+            update(&mut metadata);
+            if let Err(error) = self.db.save_metadata(&Vec::from([&metadata])) {
+                log::error!("Error saving metadata: {error}");
+            }
+        }
+
+        if self.open_document(&path).is_ok() {
+            let new_page = self.provider.chunk_cache().and_then(|cache| {
+                cache
+                    .pages
+                    .iter()
+                    .position(|it| Some(&it.name) == page_name.as_ref())
+            });
+
+            self.current_chunk_index = 0;
+            self.target_page = Some(new_page.unwrap_or(page));
+        }
+    }
+
+    //Move to the target page once the provider segmented it, on its first chunk
+    //or the one closest to the target rect
+    fn resolve_target_page(&mut self) {
+        let page = match self.target_page {
+            Some(it) => it,
+            None => return,
+        };
+
+        let mut best: Option<(usize, f32)> = None;
+
+        for index in 0..self.provider.chunk_count() {
+            match self.provider.get_chunk(index) {
+                Some(chunk) if chunk.texture_index == page && self.target_rect.is_some() => {
+                    let score = rect_affinity(&chunk.rect, &self.target_rect.unwrap());
+                    if best.is_none_or(|(_, best_score)| score > best_score) {
+                        best = Some((index, score));
+                    }
+                }
+                Some(chunk) if chunk.texture_index >= page => {
+                    self.current_chunk_index = best.map_or(index, |(best_index, _)| best_index);
+                    self.target_page = None;
+                    self.target_rect = None;
+                    return;
+                }
+                Some(_) => {}
+                //Still segmenting, try again on the next frame
+                None => return,
+            }
+        }
+
+        if self.provider.done_processing() {
+            if let Some((index, _)) = best {
+                self.current_chunk_index = index;
+            }
+            self.target_page = None;
+            self.target_rect = None;
+        }
+    }
+}
+
+//Size a chunk takes on screen: fitting whole when possible, else scrollable
+fn chunk_screen_size(chunk: &Chunk, screen_rect: &Rectangle) -> Vector2 {
+    //Tall chunks are shrunk to fit vertically, but not narrower than this fraction of the screen
+    const MIN_WIDTH_RATIO: f32 = 0.6;
+
+    let width_scale = screen_rect.width / chunk.rect.width;
+    let height_scale = screen_rect.height / chunk.rect.height;
+
+    let scale = if chunk.rect.height * width_scale <= screen_rect.height {
+        width_scale
+    } else {
+        height_scale.max(width_scale * MIN_WIDTH_RATIO)
+    };
+
+    Vector2::new(chunk.rect.width * scale, chunk.rect.height * scale)
+}
+
+//How well a chunk matches a remembered rect: the overlapping area, or minus the distance between them
+fn rect_affinity(chunk: &Rectangle, target: &Rectangle) -> f32 {
+    if let Some(overlap) = chunk.get_collision_rec(target) {
+        return overlap.width * overlap.height;
+    }
+
+    let chunk_center = Vector2::new(chunk.x + chunk.width / 2.0, chunk.y + chunk.height / 2.0);
+    let target_center = Vector2::new(
+        target.x + target.width / 2.0,
+        target.y + target.height / 2.0,
+    );
+
+    -chunk_center.distance_to(target_center)
+}
+
+//Where the next volume prompt is shown, above the progress dots
+fn next_volume_rect(screen_rect: &Rectangle) -> Rectangle {
+    let width = 320.0_f32.min(screen_rect.width - 20.0);
+
+    Rectangle::new(
+        screen_rect.x + (screen_rect.width - width) / 2.0,
+        screen_rect.y + screen_rect.height - 100.0,
+        width,
+        64.0,
+    )
 }
 
 pub fn get_time() -> u64 {
@@ -921,6 +1791,37 @@ pub fn get_time() -> u64 {
         .as_secs()
 }
 
+//Contents of a nul terminated text box buffer
+fn buffer_text(buffer: &[u8]) -> String {
+    let length = buffer
+        .iter()
+        .position(|it| *it == 0)
+        .unwrap_or(buffer.len());
+
+    String::from_utf8_lossy(&buffer[..length]).to_string()
+}
+
+//Five pointed star, used to mark favourites
+fn draw_star(context: &mut RaylibDrawHandle, center: Vector2, radius: f32, color: Color) {
+    //Triangle fans are drawn counter-clockwise, angles grow clockwise on screen
+    let mut points = vec![center];
+
+    for index in 0..=10 {
+        let angle = (-90.0 - index as f32 * 36.0).to_radians();
+        let distance = if index % 2 == 0 {
+            radius
+        } else {
+            radius * 0.45
+        };
+        points.push(Vector2::new(
+            center.x + angle.cos() * distance,
+            center.y + angle.sin() * distance,
+        ));
+    }
+
+    context.draw_triangle_fan(&points, color);
+}
+
 fn draw_text_centered(
     context: &mut RaylibDrawHandle,
     text: &str,
@@ -0,0 +1,330 @@
+use std::ffi::CString;
+
+use raylib::prelude::*;
+
+use crate::structs::{Bookmark, Chunk};
+
+use super::{buffer_text, draw_text_centered, get_time, Application};
+
+//Width of the jump list on the right side of the viewer
+const PANEL_WIDTH: f32 = 260.0;
+
+//Height of every jump list row
+const ROW_HEIGHT: f32 = 24.0;
+
+//Notes longer than this are cut in the jump list
+const NOTE_PREVIEW_LENGTH: usize = 28;
+
+//Bookmarks of the open document, along with the jump list and note editor state
+pub struct Bookmarks {
+    list: Vec<Bookmark>,
+    show_list: bool,
+    scroll: Vector2,
+    //Bookmark whose note is being edited, by index in list
+    editing: Option<usize>,
+    //Text box contents, nul terminated
+    note_buffer: [u8; 256],
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self {
+            list: Vec::new(),
+            show_list: false,
+            scroll: Vector2::zero(),
+            editing: None,
+            note_buffer: [0; 256],
+        }
+    }
+
+    //Replace the bookmarks with those of a newly opened document
+    pub fn set(&mut self, list: Vec<Bookmark>) {
+        self.list = list;
+        self.scroll = Vector2::zero();
+        self.editing = None;
+    }
+
+    pub fn toggle_list(&mut self) {
+        self.show_list = !self.show_list;
+    }
+
+    //The note editor takes every key while it's open
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    //Index of the bookmark placed on the given chunk
+    fn on_chunk(&self, chunk: &Chunk) -> Option<usize> {
+        self.list.iter().position(|it| marks_chunk(it, chunk))
+    }
+
+    fn note_text(&self) -> String {
+        buffer_text(&self.note_buffer)
+    }
+}
+
+//Chunks are segmented again when the cache is rebuilt, a bookmark marks the chunk holding its center
+fn marks_chunk(bookmark: &Bookmark, chunk: &Chunk) -> bool {
+    let center = Vector2::new(
+        bookmark.rect.x + bookmark.rect.width / 2.0,
+        bookmark.rect.y + bookmark.rect.height / 2.0,
+    );
+
+    bookmark.texture_index == chunk.texture_index && chunk.rect.check_collision_point_rec(center)
+}
+
+//Where the jump list is drawn
+fn panel_rect(screen_rect: &Rectangle) -> Rectangle {
+    Rectangle::new(
+        screen_rect.x + screen_rect.width - PANEL_WIDTH,
+        screen_rect.y,
+        PANEL_WIDTH,
+        screen_rect.height - 30.0,
+    )
+}
+
+//Where the note editor is drawn
+fn editor_rect(screen_rect: &Rectangle) -> Rectangle {
+    let width = 400.0_f32.min(screen_rect.width - 20.0);
+
+    Rectangle::new(
+        screen_rect.x + (screen_rect.width - width) / 2.0,
+        screen_rect.y + screen_rect.height - 100.0,
+        width,
+        64.0,
+    )
+}
+
+impl Application {
+    //Add a bookmark on the current chunk, or remove the one already there
+    pub(super) fn toggle_bookmark(&mut self) {
+        let (path, chunk) = match (&self.current_document_path, self.current_chunk) {
+            (Some(path), Some(chunk)) => (path.clone(), chunk),
+            _ => return,
+        };
+
+        match self.bookmarks.on_chunk(&chunk) {
+            Some(index) => {
+                let bookmark = self.bookmarks.list.remove(index);
+                self.db.remove_bookmark(&path, &bookmark);
+                self.bookmarks.editing = None;
+            }
+            None => {
+                let bookmark = Bookmark {
+                    texture_index: chunk.texture_index,
+                    rect: chunk.rect,
+                    note: String::new(),
+                    created: get_time(),
+                };
+                self.db.save_bookmark(&path, &bookmark);
+                self.bookmarks.set(self.db.bookmarks_for(&path));
+            }
+        }
+    }
+
+    //Edit the note of the current chunk's bookmark, adding one if needed
+    pub(super) fn edit_bookmark_note(&mut self) {
+        let chunk = match self.current_chunk {
+            Some(it) => it,
+            None => return,
+        };
+
+        if self.bookmarks.on_chunk(&chunk).is_none() {
+            self.toggle_bookmark();
+        }
+
+        if let Some(index) = self.bookmarks.on_chunk(&chunk) {
+            let note = self.bookmarks.list[index].note.as_bytes();
+            let length = note.len().min(self.bookmarks.note_buffer.len() - 1);
+
+            self.bookmarks.note_buffer = [0; 256];
+            self.bookmarks.note_buffer[..length].copy_from_slice(&note[..length]);
+            self.bookmarks.editing = Some(index);
+
+            //The key opening the editor would otherwise be typed into it
+            while unsafe { raylib::ffi::GetCharPressed() } > 0 {}
+        }
+    }
+
+    //Move to the chunk a bookmark resolves to, once its page is segmented
+    fn jump_to_bookmark(&mut self, index: usize) {
+        if let Some(bookmark) = self.bookmarks.list.get(index) {
+            self.target_page = Some(bookmark.texture_index);
+            self.target_rect = Some(bookmark.rect);
+            self.scroll = 0.0;
+        }
+    }
+
+    //Clicks over the jump list or note editor aren't page turns
+    pub(super) fn bookmarks_hovered(&self, screen_rect: &Rectangle, point: Vector2) -> bool {
+        (self.bookmarks.show_list && panel_rect(screen_rect).check_collision_point_rec(point))
+            || (self.bookmarks.is_editing()
+                && editor_rect(screen_rect).check_collision_point_rec(point))
+    }
+
+    //Draw the current chunk's bookmark, the jump list and the note editor
+    pub(super) fn draw_bookmarks(
+        &mut self,
+        screen_rect: &Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) {
+        let current = self
+            .current_chunk
+            .and_then(|chunk| self.bookmarks.on_chunk(&chunk));
+
+        if let Some(index) = current {
+            //Marker on the top right corner
+            let x = screen_rect.x + screen_rect.width - 30.0;
+            let y = screen_rect.y;
+            context.draw_rectangle(x as i32, y as i32, 16, 24, Color::ORANGE);
+            context.draw_triangle(
+                Vector2::new(x, y + 24.0),
+                Vector2::new(x + 8.0, y + 32.0),
+                Vector2::new(x + 16.0, y + 24.0),
+                Color::ORANGE,
+            );
+
+            let note = &self.bookmarks.list[index].note;
+            if !note.is_empty() && !self.bookmarks.is_editing() {
+                draw_text_centered(
+                    context,
+                    note.as_str(),
+                    Rectangle::new(screen_rect.x, screen_rect.y, screen_rect.width, 24.0),
+                    self.fonts.default(),
+                    Color::DARKGRAY,
+                );
+            }
+        }
+
+        if self.bookmarks.show_list {
+            self.draw_jump_list(screen_rect, context);
+        }
+
+        if let Some(index) = self.bookmarks.editing {
+            let rect = editor_rect(screen_rect);
+
+            context.draw_rectangle_rec(rect, Color::WHITE.fade(0.9));
+            context.draw_rectangle_lines_ex(rect, 1, Color::DARKGRAY);
+            draw_text_centered(
+                context,
+                "Bookmark note (Enter to save, Escape to cancel)",
+                Rectangle::new(rect.x, rect.y + 5.0, rect.width, 24.0),
+                self.fonts.default(),
+                Color::BLACK,
+            );
+
+            let submitted = context.gui_text_box(
+                Rectangle::new(rect.x + 10.0, rect.y + 34.0, rect.width - 20.0, 24.0),
+                &mut self.bookmarks.note_buffer,
+                true,
+            );
+
+            if context.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+                self.bookmarks.editing = None;
+            } else if submitted {
+                self.bookmarks.editing = None;
+
+                let note = self.bookmarks.note_text();
+                if let (Some(path), Some(bookmark)) = (
+                    &self.current_document_path,
+                    self.bookmarks.list.get_mut(index),
+                ) {
+                    bookmark.note = note;
+                    self.db.save_bookmark(path, bookmark);
+                }
+            }
+        }
+    }
+
+    //Every bookmark of the document, clicking one jumps to it
+    fn draw_jump_list(&mut self, screen_rect: &Rectangle, context: &mut RaylibDrawHandle) {
+        let rect = panel_rect(screen_rect);
+
+        context.draw_rectangle_rec(rect, Color::WHITE.fade(0.95));
+        context.draw_rectangle_lines_ex(rect, 1, Color::LIGHTGRAY);
+        draw_text_centered(
+            context,
+            "Bookmarks",
+            Rectangle::new(rect.x, rect.y, rect.width, ROW_HEIGHT),
+            self.fonts.bold(),
+            Color::BLACK,
+        );
+
+        let list_rect = Rectangle::new(
+            rect.x,
+            rect.y + ROW_HEIGHT + 6.0,
+            rect.width,
+            rect.height - ROW_HEIGHT - 6.0,
+        );
+
+        if self.bookmarks.list.is_empty() {
+            draw_text_centered(
+                context,
+                "Press B to bookmark a chunk",
+                list_rect,
+                self.fonts.default(),
+                Color::DARKGRAY,
+            );
+            return;
+        }
+
+        let content_rect = Rectangle::new(
+            list_rect.x,
+            list_rect.y,
+            list_rect.width - 14.0,
+            self.bookmarks.list.len() as f32 * (ROW_HEIGHT + 2.0),
+        );
+
+        let (view, scroll) =
+            context.gui_scroll_panel(list_rect, content_rect, self.bookmarks.scroll);
+        self.bookmarks.scroll = scroll;
+
+        let mouse_in_view = view.check_collision_point_rec(context.get_mouse_position());
+        let mut jump_to: Option<usize> = None;
+
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                view.x as i32,
+                view.y as i32,
+                view.width as i32,
+                view.height as i32,
+            );
+        }
+
+        for (index, bookmark) in self.bookmarks.list.iter().enumerate() {
+            let row = Rectangle::new(
+                view.x + 4.0,
+                view.y + scroll.y + index as f32 * (ROW_HEIGHT + 2.0),
+                view.width - 8.0,
+                ROW_HEIGHT,
+            );
+
+            if row.y + row.height < view.y || row.y > view.y + view.height {
+                continue;
+            }
+
+            let note: String = bookmark.note.chars().take(NOTE_PREVIEW_LENGTH).collect();
+            let label = if note.is_empty() {
+                format!("Page {}", bookmark.texture_index + 1)
+            } else {
+                format!("Page {}: {note}", bookmark.texture_index + 1)
+            };
+
+            if context.gui_button(
+                row,
+                Some(CString::new(label).unwrap_or_default().as_c_str()),
+            ) && mouse_in_view
+            {
+                jump_to = Some(index);
+            }
+        }
+
+        unsafe {
+            raylib::ffi::EndScissorMode();
+        }
+
+        if let Some(index) = jump_to {
+            self.jump_to_bookmark(index);
+        }
+    }
+}
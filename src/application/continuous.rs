@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+
+use raylib::prelude::*;
+
+use crate::structs::{ReadingDirection, ViewMode};
+
+use super::{
+    draw_text_centered, next_volume_rect, zoom::wheel_move, Application, DOTS_SHOW_TIMEOUT,
+};
+
+//Share of the screen scrolled by each wheel step
+const WHEEL_STEP: f32 = 0.1;
+
+//Share of the screen scrolled by page up/down and clicks
+const PAGE_STEP: f32 = 0.9;
+
+//Screens per second scrolled while up or down is held
+const KEY_SCROLL_SPEED: f32 = 1.5;
+
+//Share of the pending scroll done each frame, the rest eases out on the next ones
+const SCROLL_EASING: f32 = 0.3;
+
+//Height to width ratio assumed for pages not loaded yet, until one is
+const DEFAULT_PAGE_RATIO: f32 = 1.5;
+
+impl Application {
+    //Switch between the continuous strip and a single page, from chunk mode too
+    pub(super) fn toggle_continuous(&mut self) {
+        match self.view_mode {
+            ViewMode::Chunk => {
+                //Opens on the current chunk's page, scrolled to the chunk
+                self.toggle_view_mode();
+                self.strip_offset = 0.0;
+                self.view_mode = ViewMode::Continuous;
+            }
+            ViewMode::Page | ViewMode::Spread => {
+                self.strip_offset = 0.0;
+                self.page_focus = None;
+                self.view_mode = ViewMode::Continuous;
+            }
+            ViewMode::Continuous => {
+                //Keep the same part of the page on screen
+                self.page_scroll = Vector2::zero();
+                self.page_focus = self.page_view_rect;
+                self.view_mode = ViewMode::Page;
+            }
+        }
+
+        self.strip_pending_scroll = 0.0;
+        self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+    }
+
+    //Draw every page stacked in one strip as wide as the screen, from current_page down
+    pub(super) fn draw_continuous(
+        &mut self,
+        screen_rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) {
+        //A jump (bookmark, reopening) moves to its page right away
+        if let Some(page) = self.target_page {
+            if self.current_page != Some(page) {
+                self.current_page = Some(page);
+                self.strip_offset = 0.0;
+                self.strip_pending_scroll = 0.0;
+            }
+        }
+
+        let page = match self.resolve_current_page() {
+            Some(it) => it,
+            None => {
+                draw_text_centered(
+                    context,
+                    "Loading...",
+                    screen_rect,
+                    self.fonts.large(),
+                    Color::BLACK,
+                );
+                return;
+            }
+        };
+
+        //Scroll to the chunk the reader was on before switching from chunk mode
+        if let Some(focus) = self.page_focus {
+            if let Some(size) = self.page_sizes.get(&page) {
+                self.strip_offset = (focus.y / size.y).clamp(0.0, 1.0);
+                self.page_focus = None;
+            }
+        }
+
+        if !self.bookmarks.is_editing() {
+            self.handle_continuous_input(context, &screen_rect);
+        }
+
+        //The input handler may have toggled the mode or opened another document
+        if self.view_mode != ViewMode::Continuous || self.current_page.is_none() {
+            return;
+        }
+
+        let pending = self.strip_pending_scroll;
+        let step = if pending.abs() < 1.0 {
+            pending
+        } else {
+            pending * SCROLL_EASING
+        };
+        self.strip_pending_scroll -= step;
+        self.scroll_strip(step, &screen_rect);
+
+        //Progress follows the page at the top of the screen
+        let previous_page = page;
+        let page = self.current_page.unwrap_or(page);
+        if page != previous_page {
+            self.target_page = Some(page);
+            self.target_rect = None;
+            self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+        }
+
+        let width = screen_rect.width;
+        let ratio = self.estimated_page_ratio();
+        let page_count = self.provider.page_count();
+
+        //The strip as laid out without zoom is magnified as a whole, keeping the pan while scrolling
+        self.zoom.show(ViewMode::Continuous, 0);
+        let source_width = self.page_sizes.get(&page).map_or(width, |size| size.x);
+        let view = self.zoom.fit(screen_rect, source_width, &screen_rect);
+        let scale = view.width / width;
+        let zoomed_y = |y: f32| view.y + (y - screen_rect.y) * scale;
+        let zoomed = |rect: Rectangle| {
+            Rectangle::new(
+                view.x + (rect.x - screen_rect.x) * scale,
+                zoomed_y(rect.y),
+                rect.width * scale,
+                rect.height * scale,
+            )
+        };
+        let screen_bottom = screen_rect.y + screen_rect.height;
+
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                screen_rect.x as i32,
+                screen_rect.y as i32,
+                screen_rect.width as i32,
+                screen_rect.height as i32,
+            );
+        }
+
+        let mut y = screen_rect.y
+            - self.strip_offset * strip_page_height(&self.page_sizes, page, width, ratio);
+        let mut index = page;
+
+        //Zoomed out, the pages above the current one show too
+        while index > 0 && zoomed_y(y) > screen_rect.y {
+            index -= 1;
+            y -= strip_page_height(&self.page_sizes, index, width, ratio);
+        }
+        let first_page = index;
+
+        let mut drawn: Vec<(usize, Rectangle, Rectangle)> = Vec::new();
+        while index < page_count && zoomed_y(y) < screen_bottom {
+            let height = strip_page_height(&self.page_sizes, index, width, ratio);
+            let target_rect = zoomed(Rectangle::new(screen_rect.x, y, width, height));
+
+            match self.textures.get(&index) {
+                Some(Some(texture)) => {
+                    let source =
+                        Rectangle::new(0.0, 0.0, texture.width() as f32, texture.height() as f32);
+                    context.draw_texture_pro(
+                        texture,
+                        source,
+                        target_rect,
+                        Vector2::zero(),
+                        0f32,
+                        Color::WHITE,
+                    );
+                    drawn.push((index, source, target_rect));
+                }
+                Some(None) => context.draw_rectangle_rec(target_rect, Color::LIGHTGRAY),
+                None => {
+                    self.image_queries.push(index);
+                    context.draw_rectangle_rec(target_rect, Color::LIGHTGRAY.fade(0.5));
+                }
+            }
+
+            if let Some(session) = self.session.as_mut() {
+                session.record_page(index);
+            }
+
+            self.shown_textures.push(index);
+            y += height;
+            index += 1;
+        }
+
+        //Over every page, the one under the mouse is magnified
+        for (index, source, dest) in drawn {
+            if let Some(Some(texture)) = self.textures.get(&index) {
+                self.zoom.draw_loupe(context, texture, source, dest);
+            }
+        }
+
+        unsafe {
+            raylib::ffi::EndScissorMode();
+        }
+
+        self.zoom
+            .draw_level(context, &screen_rect, self.fonts.default());
+
+        //Load the pages just outside the screen ahead of time
+        for neighbour in [
+            first_page.checked_sub(1),
+            (index < page_count).then_some(index),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !self.textures.contains_key(&neighbour) {
+                self.image_queries.push(neighbour);
+            }
+            self.shown_textures.push(neighbour);
+        }
+
+        //Part of the top page on screen, to land on the matching chunk in chunk mode
+        self.page_view_rect = self.page_sizes.get(&page).and_then(|size| {
+            let scale = width / size.x;
+
+            (self.strip_offset > 0.0).then(|| {
+                Rectangle::new(
+                    0.0,
+                    self.strip_offset * size.y,
+                    size.x,
+                    (screen_rect.height / scale).min(size.y * (1.0 - self.strip_offset)),
+                )
+            })
+        });
+
+        if self.draw_next_volume(&screen_rect, context) {
+            return;
+        }
+
+        self.draw_bookmarks(&screen_rect, context);
+
+        if self.show_dots_timeout > 0.0 {
+            let indicator = format!(
+                "{} / {} ({})",
+                page + 1,
+                page_count,
+                ViewMode::Continuous.label()
+            );
+
+            self.draw_page_indicator(indicator.as_str(), &screen_rect, context);
+        }
+    }
+
+    //Average height to width ratio of the pages loaded so far
+    fn estimated_page_ratio(&self) -> f32 {
+        if self.page_sizes.is_empty() {
+            return DEFAULT_PAGE_RATIO;
+        }
+
+        self.page_sizes.values().map(|it| it.y / it.x).sum::<f32>() / self.page_sizes.len() as f32
+    }
+
+    //Move the strip down by a number of pixels (up if negative), returns whether its end is on screen
+    fn scroll_strip(&mut self, delta: f32, screen_rect: &Rectangle) -> bool {
+        let mut page = match self.current_page {
+            Some(it) => it,
+            None => return false,
+        };
+
+        let width = screen_rect.width;
+        let ratio = self.estimated_page_ratio();
+        let last_page = self.provider.page_count().saturating_sub(1);
+        let height = |index: usize| strip_page_height(&self.page_sizes, index, width, ratio);
+
+        //Offset from the top of the page at the top of the screen, in pixels
+        let mut offset = self.strip_offset * height(page) + delta;
+
+        while offset >= height(page) && page < last_page {
+            offset -= height(page);
+            page += 1;
+        }
+
+        //Stop once the bottom of the last page reaches the bottom of the screen
+        let mut below = -offset;
+        let mut index = page;
+        while index <= last_page && below < screen_rect.height {
+            below += height(index);
+            index += 1;
+        }
+
+        let at_end = below <= screen_rect.height;
+        if below < screen_rect.height {
+            offset -= screen_rect.height - below;
+        }
+
+        while offset < 0.0 && page > 0 {
+            page -= 1;
+            offset += height(page);
+        }
+
+        let offset = offset.max(0.0);
+
+        self.strip_offset = offset / height(page);
+        self.current_page = Some(page);
+
+        at_end
+    }
+
+    //Handle user input in continuous mode
+    fn handle_continuous_input(&mut self, context: &mut RaylibDrawHandle, screen_rect: &Rectangle) {
+        self.zoom.update(context, screen_rect);
+
+        if self.handle_mode_keys(context) {
+            return;
+        }
+
+        //Held keys scroll steadily, without easing
+        let key_scroll = screen_rect.height * KEY_SCROLL_SPEED * context.get_frame_time();
+        if context.is_key_down(KeyboardKey::KEY_DOWN) {
+            self.strip_pending_scroll = 0.0;
+            self.scroll_strip(key_scroll, screen_rect);
+        } else if context.is_key_down(KeyboardKey::KEY_UP) {
+            self.strip_pending_scroll = 0.0;
+            self.scroll_strip(-key_scroll, screen_rect);
+        }
+
+        let wheel = wheel_move(context);
+        self.strip_pending_scroll -= wheel * WHEEL_STEP * (context.get_screen_height() as f32);
+
+        //Right to left documents advance towards the left
+        let right_to_left = self.reading_direction == ReadingDirection::RightToLeft;
+        let (next_key, previous_key) = if right_to_left {
+            (KeyboardKey::KEY_LEFT, KeyboardKey::KEY_RIGHT)
+        } else {
+            (KeyboardKey::KEY_RIGHT, KeyboardKey::KEY_LEFT)
+        };
+
+        //Clicks on the next volume prompt and bookmarks are handled by their buttons,
+        //a click is only known once it can't be a double click anymore
+        let click = self.zoom.take_click().filter(|point| {
+            !((self.next_volume.is_some()
+                && next_volume_rect(screen_rect).check_collision_point_rec(*point))
+                || self.bookmarks_hovered(screen_rect, *point))
+        });
+
+        let clicked = click.is_some();
+        let left_half =
+            click.is_some_and(|point| point.x < screen_rect.x + screen_rect.width / 2.0);
+
+        let forward = context.is_key_pressed(KeyboardKey::KEY_PAGE_DOWN)
+            || context.is_key_pressed(next_key)
+            || (clicked && left_half == right_to_left);
+        let backward = context.is_key_pressed(KeyboardKey::KEY_PAGE_UP)
+            || context.is_key_pressed(previous_key)
+            || (clicked && left_half != right_to_left);
+
+        if forward {
+            self.strip_pending_scroll += screen_rect.height * PAGE_STEP;
+        } else if backward {
+            self.strip_pending_scroll -= screen_rect.height * PAGE_STEP;
+        }
+
+        //Moving down past the end of the strip
+        let moving_down = forward || wheel < 0.0 || context.is_key_pressed(KeyboardKey::KEY_DOWN);
+        let moving_up = backward || wheel > 0.0 || context.is_key_pressed(KeyboardKey::KEY_UP);
+
+        if moving_down && self.scroll_strip(0.0, screen_rect) {
+            self.strip_pending_scroll = 0.0;
+            self.next_volume = self
+                .current_document_path
+                .as_ref()
+                .and_then(|path| self.db.next_volume(path));
+        } else if moving_up {
+            self.next_volume = None;
+        }
+    }
+}
+
+//Height a page takes in the strip, guessed from the other pages until it's loaded
+fn strip_page_height(sizes: &HashMap<usize, Vector2>, index: usize, width: f32, ratio: f32) -> f32 {
+    match sizes.get(&index) {
+        Some(size) => width * size.y / size.x,
+        None => width * ratio,
+    }
+}
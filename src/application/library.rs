@@ -0,0 +1,650 @@
+use std::{collections::HashMap, ffi::CString};
+
+use raylib::prelude::*;
+
+use crate::{
+    structs::{ComicMetadata, LibraryEntry, LibraryFilter, LibraryQuery, LibrarySort, Series},
+    transfer::{export_progress, import_progress},
+};
+
+use super::{
+    buffer_text, draw_text_centered, Application, CardAction, Screen, CARD_HEIGHT, CARD_SPACING,
+    CARD_WIDTH,
+};
+
+//Height of the search/sort bar on top of the library
+const TOOLBAR_HEIGHT: f32 = 24.0;
+
+//Width of every sort order toggle
+const SORT_ITEM_WIDTH: f32 = 90.0;
+
+//Width of the scroll panel's scrollbar
+const SCROLLBAR_WIDTH: f32 = 14.0;
+
+//Height of every library folder row
+const ROOT_ROW_HEIGHT: f32 = 24.0;
+
+//Every document of the database, searchable and sorted, fetched one window at a time
+pub struct Library {
+    query: LibraryQuery,
+    //Text box contents, nul terminated
+    search_buffer: [u8; 128],
+    search_editing: bool,
+    //Entries currently in view, starting at query.offset
+    results: Vec<LibraryEntry>,
+    //How many documents match the search
+    total: usize,
+    //Results must be fetched again
+    stale: bool,
+    scroll: Vector2,
+    //Thumbnails of the documents in view, by path
+    thumbs: HashMap<String, Texture2D>,
+    //Thumbnails waiting to be uploaded as textures
+    pending_thumbs: Vec<(String, Vec<u8>)>,
+    //Show the library folders instead of the documents
+    show_roots: bool,
+    //Library folders, None to read them again from the database
+    roots: Option<Vec<String>>,
+    //Series whose volumes are listed, None to list every series and document
+    series: Option<Series>,
+    //Filters offered in the dropdown, None to read tags and collections again
+    filters: Option<Vec<(LibraryFilter, String)>>,
+    filter_editing: bool,
+    //Outcome of the last progress export or import
+    transfer_status: Option<String>,
+}
+
+impl Library {
+    pub fn new() -> Self {
+        Self {
+            query: LibraryQuery {
+                group_series: true,
+                ..LibraryQuery::default()
+            },
+            search_buffer: [0; 128],
+            search_editing: false,
+            results: Vec::new(),
+            total: 0,
+            stale: true,
+            scroll: Vector2::zero(),
+            thumbs: HashMap::new(),
+            pending_thumbs: Vec::new(),
+            show_roots: false,
+            roots: None,
+            series: None,
+            filters: None,
+            filter_editing: false,
+            transfer_status: None,
+        }
+    }
+
+    //List the volumes of a series, or every entry again with None
+    fn show_series(&mut self, series: Option<Series>) {
+        self.query.series = series.as_ref().map(|it| it.id);
+        self.series = series;
+        self.scroll = Vector2::zero();
+        self.stale = true;
+    }
+
+    //Fetch the results again next time the library is drawn, after documents changed
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+        self.filters = None;
+    }
+
+    //Upload the thumbnails of newly fetched documents
+    pub fn load_thumbnails(&mut self, context: &mut RaylibHandle, thread: &RaylibThread) {
+        for (path, data) in self.pending_thumbs.drain(..) {
+            match Image::load_image_from_mem(".jpg", &data, data.len() as i32) {
+                Ok(image) => match context.load_texture_from_image(thread, &image) {
+                    Ok(texture) => {
+                        self.thumbs.insert(path, texture);
+                    }
+                    Err(err) => eprintln!("Error loading texture: '{err:?}'!"),
+                },
+                Err(err) => eprintln!("Error loading image: '{err:?}'!"),
+            }
+        }
+    }
+
+    fn search_text(&self) -> String {
+        buffer_text(&self.search_buffer)
+    }
+}
+
+//Where the filter dropdown is drawn, between the search box and sort order
+fn filter_rect(screen_rect: &Rectangle) -> Rectangle {
+    let width = 150.0;
+
+    Rectangle::new(
+        screen_rect.x + screen_rect.width - 3.0 * (SORT_ITEM_WIDTH + 2.0) - width - 10.0,
+        screen_rect.y,
+        width,
+        TOOLBAR_HEIGHT,
+    )
+}
+
+impl Application {
+    //Draw the library screen, returns true while it's shown
+    pub(super) fn library(
+        &mut self,
+        screen_rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) -> bool {
+        self.library_contents(screen_rect, context);
+
+        //Drawn last, so the open dropdown covers the cards
+        if self.screen == Screen::Library && self.provider.chunk_count() == 0 {
+            self.library_filter(filter_rect(&screen_rect), context);
+        }
+
+        true
+    }
+
+    //Every filter, along with its dropdown label
+    fn library_filters(&mut self) -> Vec<(LibraryFilter, String)> {
+        if let Some(filters) = &self.library.filters {
+            return filters.clone();
+        }
+
+        let mut filters = vec![
+            (LibraryFilter::All, "All".to_string()),
+            (LibraryFilter::Favourites, "Favourites".to_string()),
+        ];
+        filters.extend(
+            self.db
+                .tags()
+                .into_iter()
+                .map(|it| (LibraryFilter::Tag(it.clone()), format!("Tag: {it}"))),
+        );
+        filters.extend(self.db.collections().into_iter().map(|it| {
+            (
+                LibraryFilter::Collection(it.id),
+                format!("Collection: {}", it.name),
+            )
+        }));
+
+        //Dropdown items are separated by ';'
+        for (_, label) in filters.iter_mut() {
+            *label = label.replace(';', ",");
+        }
+
+        self.library.filters = Some(filters.clone());
+        filters
+    }
+
+    //Dropdown choosing the favourites, a tag or a collection to list
+    fn library_filter(&mut self, rect: Rectangle, context: &mut RaylibDrawHandle) {
+        let filters = self.library_filters();
+
+        //A deleted tag or collection no longer filters anything
+        let mut active = match filters
+            .iter()
+            .position(|(it, _)| *it == self.library.query.filter)
+        {
+            Some(it) => it as i32,
+            None => {
+                self.library.query.filter = LibraryFilter::All;
+                self.library.stale = true;
+                0
+            }
+        };
+
+        let labels: Vec<&str> = filters.iter().map(|(_, label)| label.as_str()).collect();
+
+        if context.gui_dropdown_box(
+            rect,
+            Some(
+                CString::new(labels.join(";"))
+                    .unwrap_or_default()
+                    .as_c_str(),
+            ),
+            &mut active,
+            self.library.filter_editing,
+        ) {
+            self.library.filter_editing = !self.library.filter_editing;
+        }
+
+        if let Some((filter, _)) = filters.get(active as usize) {
+            if *filter != self.library.query.filter {
+                self.library.query.filter = filter.clone();
+                self.library.scroll = Vector2::zero();
+                self.library.stale = true;
+            }
+        }
+    }
+
+    fn library_contents(&mut self, screen_rect: Rectangle, context: &mut RaylibDrawHandle) {
+        //Toolbar: back button, library folders, search box, filter and sort order
+        let back_rect = Rectangle::new(screen_rect.x, screen_rect.y, 60.0, TOOLBAR_HEIGHT);
+        let roots_rect = Rectangle::new(
+            back_rect.x + back_rect.width + 10.0,
+            screen_rect.y,
+            70.0,
+            TOOLBAR_HEIGHT,
+        );
+        let sort_rect = Rectangle::new(
+            screen_rect.x + screen_rect.width - 3.0 * (SORT_ITEM_WIDTH + 2.0),
+            screen_rect.y,
+            SORT_ITEM_WIDTH,
+            TOOLBAR_HEIGHT,
+        );
+        let filter_rect = filter_rect(&screen_rect);
+        let search_rect = Rectangle::new(
+            roots_rect.x + roots_rect.width + 10.0,
+            screen_rect.y,
+            (filter_rect.x - roots_rect.x - roots_rect.width - 20.0).max(40.0),
+            TOOLBAR_HEIGHT,
+        );
+
+        if context.gui_button(back_rect, Some(CString::new("Back").unwrap().as_c_str())) {
+            if self.library.series.is_some() {
+                self.library.show_series(None);
+            } else {
+                self.screen = Screen::Lobby;
+            }
+            return;
+        }
+
+        self.library.show_roots = context.gui_toggle(
+            roots_rect,
+            Some(CString::new("Folders").unwrap().as_c_str()),
+            self.library.show_roots,
+        );
+
+        if context.gui_text_box(
+            search_rect,
+            &mut self.library.search_buffer,
+            self.library.search_editing,
+        ) {
+            self.library.search_editing = !self.library.search_editing;
+        }
+
+        let sort = LibrarySort::from_i32(
+            context.gui_toggle_group(
+                sort_rect,
+                Some(
+                    CString::new("Last opened;Title;Progress")
+                        .unwrap()
+                        .as_c_str(),
+                ),
+                self.library.query.sort.to_i32(),
+            ),
+        );
+
+        let search = self.library.search_text();
+        if search != self.library.query.search || sort != self.library.query.sort {
+            self.library.query.search = search;
+            self.library.query.sort = sort;
+            self.library.scroll = Vector2::zero();
+            self.library.stale = true;
+        }
+
+        let mut panel_rect = Rectangle::new(
+            screen_rect.x,
+            screen_rect.y + TOOLBAR_HEIGHT + 10.0,
+            screen_rect.width,
+            screen_rect.height - TOOLBAR_HEIGHT - 10.0,
+        );
+
+        if let Some(scanner) = &self.scanner {
+            panel_rect.height -= 20.0;
+            draw_text_centered(
+                context,
+                format!(
+                    "Scanning library... {} documents found",
+                    scanner.found_count()
+                )
+                .as_str(),
+                Rectangle::new(
+                    panel_rect.x,
+                    panel_rect.y + panel_rect.height,
+                    panel_rect.width,
+                    20.0,
+                ),
+                self.fonts.default(),
+                Color::DARKGRAY,
+            );
+        }
+
+        if self.library.show_roots {
+            self.library_roots(panel_rect, context);
+            return;
+        }
+
+        if let Some(series) = &self.library.series {
+            draw_text_centered(
+                context,
+                series.name.as_str(),
+                Rectangle::new(panel_rect.x, panel_rect.y, panel_rect.width, TOOLBAR_HEIGHT),
+                self.fonts.bold(),
+                Color::BLACK,
+            );
+            panel_rect.y += TOOLBAR_HEIGHT;
+            panel_rect.height -= TOOLBAR_HEIGHT;
+        }
+
+        //Grid layout, as many columns as fit
+        let row_height = (CARD_HEIGHT + CARD_SPACING) as f32;
+        let column_width = (CARD_WIDTH + CARD_SPACING) as f32;
+        let cols = (((panel_rect.width - SCROLLBAR_WIDTH) / column_width) as usize).max(1);
+        let visible_rows = (panel_rect.height / row_height) as usize + 2;
+
+        if self.library.stale {
+            self.library.total = self.db.count_library(&self.library.query);
+        }
+
+        let rows = self.library.total.div_ceil(cols);
+        let content_rect = Rectangle::new(
+            panel_rect.x,
+            panel_rect.y,
+            panel_rect.width - SCROLLBAR_WIDTH,
+            rows as f32 * row_height + CARD_SPACING as f32,
+        );
+
+        let (view, scroll) =
+            context.gui_scroll_panel(panel_rect, content_rect, self.library.scroll);
+        self.library.scroll = scroll;
+
+        //Only the documents in view are fetched from the database
+        let first_row = (-scroll.y / row_height).max(0.0) as usize;
+        let offset = first_row * cols;
+        let limit = visible_rows * cols;
+
+        if self.library.stale
+            || offset != self.library.query.offset
+            || limit != self.library.query.limit
+        {
+            self.library.query.offset = offset;
+            self.library.query.limit = limit;
+            self.library.results = self.db.query_library(&self.library.query);
+            self.library.stale = false;
+
+            //Keep the thumbnails still in view, queue the new ones
+            let documents: Vec<&ComicMetadata> =
+                self.library.results.iter().map(|it| &it.document).collect();
+            self.library
+                .thumbs
+                .retain(|path, _| documents.iter().any(|it| &it.path == path));
+            self.library.pending_thumbs = documents
+                .iter()
+                .filter(|it| !self.library.thumbs.contains_key(&it.path))
+                .filter_map(|it| Some((it.path.clone(), it.thumbnail.clone()?)))
+                .collect();
+        }
+
+        if self.library.total == 0 {
+            draw_text_centered(
+                context,
+                "No documents found",
+                view,
+                self.fonts.large(),
+                Color::BLACK,
+            );
+            return;
+        }
+
+        let x_offset =
+            view.x + (view.width - cols as f32 * column_width + CARD_SPACING as f32) / 2.0;
+        //Clicks on the open filter dropdown aren't meant for the cards below it
+        let mouse_in_view = view.check_collision_point_rec(context.get_mouse_position())
+            && !self.library.filter_editing;
+        let mut document_to_open: Option<String> = None;
+        let mut series_to_show: Option<Series> = None;
+        let mut favourite_to_toggle: Option<(String, bool)> = None;
+        let mut tags_to_edit: Option<(String, String)> = None;
+
+        //Cards scrolled partially out of view are clipped
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                view.x as i32,
+                view.y as i32,
+                view.width as i32,
+                view.height as i32,
+            );
+        }
+
+        for (i, entry) in self.library.results.iter().enumerate() {
+            let index = offset + i;
+            let (row, col) = (index / cols, index % cols);
+
+            let rect = Rectangle::new(
+                x_offset + col as f32 * column_width,
+                view.y + scroll.y + row as f32 * row_height + CARD_SPACING as f32 / 2.0,
+                CARD_WIDTH as f32,
+                CARD_HEIGHT as f32,
+            );
+
+            let thumbnail = self.library.thumbs.get(&entry.document.path);
+
+            //Series cards look like a stack of volumes, and are named after the series
+            let card = match &entry.series {
+                Some(series) => {
+                    for depth in [2.0, 1.0] {
+                        context.draw_rectangle_lines_ex(
+                            Rectangle::new(
+                                rect.x + depth * 4.0,
+                                rect.y - depth * 4.0,
+                                rect.width,
+                                rect.height,
+                            ),
+                            1,
+                            Color::LIGHTGRAY,
+                        );
+                    }
+                    context.draw_rectangle_rec(rect, Color::WHITE);
+
+                    ComicMetadata {
+                        title: format!("{} ({})", series.name, series.volume_count),
+                        ..entry.document.clone()
+                    }
+                }
+                None => entry.document.clone(),
+            };
+
+            let action = self.draw_recent_card(
+                rect,
+                context,
+                Some(&card),
+                thumbnail,
+                entry.series.is_none(),
+            );
+
+            if !mouse_in_view {
+                continue;
+            }
+
+            let document = &entry.document;
+            match action {
+                CardAction::OpenDocument => match &entry.series {
+                    Some(series) => series_to_show = Some(series.clone()),
+                    None => document_to_open = Some(document.path.clone()),
+                },
+                CardAction::ToggleFavourite => {
+                    favourite_to_toggle = Some((document.path.clone(), document.favourite))
+                }
+                CardAction::EditTags => {
+                    tags_to_edit = Some((document.path.clone(), document.title.clone()))
+                }
+                _ => {}
+            }
+        }
+
+        unsafe {
+            raylib::ffi::EndScissorMode();
+        }
+
+        if series_to_show.is_some() {
+            self.library.show_series(series_to_show);
+        }
+
+        if let Some((path, favourite)) = favourite_to_toggle {
+            self.db.set_favourite(&path, !favourite);
+            self.library.invalidate();
+            self.update_recents();
+        }
+
+        if let Some((path, title)) = tags_to_edit {
+            self.open_tag_editor(&path, &title);
+        }
+
+        if let Some(path) = document_to_open {
+            if let Err(error) = self.open_document(&path) {
+                log::error!("Error opening document: {error}");
+            }
+        }
+    }
+
+    //List of library folders, with buttons to add, remove and rescan them
+    fn library_roots(&mut self, panel_rect: Rectangle, context: &mut RaylibDrawHandle) {
+        let roots = match &self.library.roots {
+            Some(it) => it.clone(),
+            None => {
+                let roots = self.db.library_roots();
+                self.library.roots = Some(roots.clone());
+                roots
+            }
+        };
+
+        let button_width = 90.0;
+        let mut y = panel_rect.y;
+
+        if roots.is_empty() {
+            draw_text_centered(
+                context,
+                "Add folders to import every document inside them",
+                Rectangle::new(panel_rect.x, y, panel_rect.width, ROOT_ROW_HEIGHT),
+                self.fonts.default(),
+                Color::DARKGRAY,
+            );
+            y += ROOT_ROW_HEIGHT + 4.0;
+        }
+
+        for root in roots.iter() {
+            context.draw_text_ex(
+                self.fonts.default() as &Font,
+                root.as_str(),
+                Vector2::new(panel_rect.x, y + 4.0),
+                self.fonts.default().baseSize as f32,
+                0.0,
+                Color::BLACK,
+            );
+
+            if context.gui_button(
+                Rectangle::new(
+                    panel_rect.x + panel_rect.width - button_width,
+                    y,
+                    button_width,
+                    ROOT_ROW_HEIGHT,
+                ),
+                Some(CString::new("Remove").unwrap().as_c_str()),
+            ) {
+                self.db.remove_library_root(root);
+                self.library.roots = None;
+            }
+
+            y += ROOT_ROW_HEIGHT + 4.0;
+        }
+
+        if context.gui_button(
+            Rectangle::new(panel_rect.x, y + 10.0, button_width, ROOT_ROW_HEIGHT),
+            Some(CString::new("Add folder").unwrap().as_c_str()),
+        ) {
+            if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                self.db.add_library_root(folder.to_string_lossy().as_ref());
+                self.library.roots = None;
+                self.start_library_scan();
+            }
+        }
+
+        if self.scanner.is_none()
+            && !roots.is_empty()
+            && context.gui_button(
+                Rectangle::new(
+                    panel_rect.x + button_width + 10.0,
+                    y + 10.0,
+                    button_width,
+                    ROOT_ROW_HEIGHT,
+                ),
+                Some(CString::new("Rescan").unwrap().as_c_str()),
+            )
+        {
+            self.start_library_scan();
+        }
+
+        self.progress_transfer(
+            Rectangle::new(
+                panel_rect.x,
+                y + ROOT_ROW_HEIGHT + 30.0,
+                panel_rect.width,
+                ROOT_ROW_HEIGHT,
+            ),
+            context,
+        );
+    }
+
+    //Buttons carrying reading progress to and from other machines
+    fn progress_transfer(&mut self, rect: Rectangle, context: &mut RaylibDrawHandle) {
+        let button_width = 120.0;
+
+        if context.gui_button(
+            Rectangle::new(rect.x, rect.y, button_width, rect.height),
+            Some(CString::new("Export progress").unwrap().as_c_str()),
+        ) {
+            if let Some(file) = rfd::FileDialog::new()
+                .add_filter("Progress file", &["json"])
+                .set_file_name("manga-viewer-progress.json")
+                .save_file()
+            {
+                self.library.transfer_status = Some(match export_progress(&self.db, &file) {
+                    Ok(count) => format!("Exported {count} documents"),
+                    Err(error) => {
+                        log::error!("{error}");
+                        error
+                    }
+                });
+            }
+        }
+
+        if context.gui_button(
+            Rectangle::new(
+                rect.x + button_width + 10.0,
+                rect.y,
+                button_width,
+                rect.height,
+            ),
+            Some(CString::new("Import progress").unwrap().as_c_str()),
+        ) {
+            if let Some(file) = rfd::FileDialog::new()
+                .add_filter("Progress file", &["json"])
+                .pick_file()
+            {
+                self.library.transfer_status = Some(match import_progress(&mut self.db, &file) {
+                    Ok(summary) => {
+                        self.library.invalidate();
+                        self.update_recents();
+                        format!(
+                            "{} documents updated, {} more recent here, {} not in this library",
+                            summary.updated, summary.merged, summary.unmatched
+                        )
+                    }
+                    Err(error) => {
+                        log::error!("{error}");
+                        error
+                    }
+                });
+            }
+        }
+
+        if let Some(status) = &self.library.transfer_status {
+            context.draw_text_ex(
+                self.fonts.default() as &Font,
+                status.as_str(),
+                Vector2::new(rect.x + 2.0 * (button_width + 10.0), rect.y + 4.0),
+                self.fonts.default().baseSize as f32,
+                0.0,
+                Color::DARKGRAY,
+            );
+        }
+    }
+}
@@ -0,0 +1,597 @@
+use raylib::prelude::*;
+
+use crate::structs::{PageFit, ReadingDirection, ViewMode};
+
+use super::{
+    draw_text_centered, next_volume_rect, zoom::wheel_move, Application, DOTS_SHOW_TIMEOUT,
+};
+
+//Share of the screen scrolled by each key press or wheel step
+const SCROLL_STEP: f32 = 0.1;
+
+//Pages wider than tall by this much are double spreads already, and shown alone in spread mode
+const WIDE_PAGE_RATIO: f32 = 1.1;
+
+impl Application {
+    //Switch between chunk and page mode, staying on the same spot of the document
+    pub(super) fn toggle_view_mode(&mut self) {
+        match self.view_mode {
+            ViewMode::Chunk => {
+                //Open the page of the current chunk, scrolled to it
+                if let Some(chunk) = self.current_chunk {
+                    self.current_page = Some(chunk.texture_index);
+                    self.page_focus = Some(chunk.rect);
+                }
+                self.page_scroll = Vector2::zero();
+                self.view_mode = ViewMode::Page;
+            }
+            ViewMode::Page | ViewMode::Spread | ViewMode::Continuous => {
+                //Land on the chunk under what was visible, or the page's first one
+                if let Some(page) = self.current_page {
+                    self.target_page = Some(self.shown_range(page).0);
+                    self.target_rect = self.page_view_rect;
+                }
+                self.scroll = 0.0;
+                self.smoothed_scroll = 0.0;
+                self.view_mode = ViewMode::Chunk;
+            }
+        }
+
+        self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+    }
+
+    //Switch between one and two pages at a time, from chunk mode too
+    pub(super) fn toggle_spread(&mut self) {
+        match self.view_mode {
+            ViewMode::Chunk => {
+                self.toggle_view_mode();
+                self.page_focus = None;
+                self.view_mode = ViewMode::Spread;
+            }
+            ViewMode::Page | ViewMode::Continuous => self.view_mode = ViewMode::Spread,
+            ViewMode::Spread => self.view_mode = ViewMode::Page,
+        }
+
+        self.page_view_rect = None;
+        self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+    }
+
+    //Draw the current page whole, scaled according to the page fit
+    pub(super) fn draw_page(&mut self, screen_rect: Rectangle, context: &mut RaylibDrawHandle) {
+        //A jump (bookmark, reopening) moves to its page right away
+        if let Some(page) = self.target_page {
+            let shown = self.current_page.map(|it| self.shown_range(it));
+            if !shown.is_some_and(|(first, last)| (first..=last).contains(&page)) {
+                self.set_page(page, false);
+            }
+        }
+
+        let page = match self.resolve_current_page() {
+            Some(it) => it,
+            None => {
+                draw_text_centered(
+                    context,
+                    "Loading...",
+                    screen_rect,
+                    self.fonts.large(),
+                    Color::BLACK,
+                );
+                return;
+            }
+        };
+
+        //Pages on screen, from left to right
+        let pages = self.shown_pages(page);
+        self.shown_textures = pages.clone();
+
+        //Sizes of the shown pages, once every one of them is loaded
+        let mut sizes: Vec<Vector2> = Vec::new();
+        for index in pages.iter() {
+            if let Some(session) = self.session.as_mut() {
+                session.record_page(*index);
+            }
+
+            match self.textures.get(index) {
+                Some(Some(texture)) => sizes.push(Vector2::new(
+                    texture.width() as f32,
+                    texture.height() as f32,
+                )),
+                Some(None) => {}
+                None => self.image_queries.push(*index),
+            }
+        }
+        let size = (sizes.len() == pages.len()).then(|| spread_size(&sizes));
+
+        let view_mode = self.view_mode;
+        if !self.bookmarks.is_editing() {
+            self.handle_page_input(context, &screen_rect, size);
+        }
+
+        //The input handler may have toggled the mode or opened another document
+        if self.view_mode != view_mode || self.current_page != Some(page) {
+            return;
+        }
+
+        //Chunk rects only map onto a page shown alone
+        if pages.len() > 1 {
+            self.page_focus = None;
+        }
+
+        match size {
+            Some(size) => {
+                let target_rect = self.page_target_rect(size, &screen_rect);
+
+                self.zoom.show(self.view_mode, self.shown_range(page).0);
+                let target_rect = self.zoom.fit(target_rect, size.x, &screen_rect);
+                let scale = target_rect.width / size.x;
+
+                unsafe {
+                    raylib::ffi::BeginScissorMode(
+                        screen_rect.x as i32,
+                        screen_rect.y as i32,
+                        screen_rect.width as i32,
+                        screen_rect.height as i32,
+                    );
+                }
+
+                //Facing pages are scaled to the same height
+                let mut drawn: Vec<(usize, Rectangle, Rectangle)> = Vec::new();
+                let mut x = target_rect.x;
+                for (index, page_size) in pages.iter().zip(sizes.iter()) {
+                    let width = page_size.x * size.y / page_size.y * scale;
+                    let source = Rectangle::new(0.0, 0.0, page_size.x, page_size.y);
+                    let dest = Rectangle::new(x, target_rect.y, width, target_rect.height);
+
+                    if let Some(Some(texture)) = self.textures.get(index) {
+                        context.draw_texture_pro(
+                            texture,
+                            source,
+                            dest,
+                            Vector2::zero(),
+                            0f32,
+                            Color::WHITE,
+                        );
+                        drawn.push((*index, source, dest));
+                    }
+
+                    x += width;
+                }
+
+                //Over every page, the one under the mouse is magnified
+                for (index, source, dest) in drawn {
+                    if let Some(Some(texture)) = self.textures.get(&index) {
+                        self.zoom.draw_loupe(context, texture, source, dest);
+                    }
+                }
+
+                unsafe {
+                    raylib::ffi::EndScissorMode();
+                }
+
+                self.zoom
+                    .draw_level(context, &screen_rect, self.fonts.default());
+
+                self.page_view_rect = if pages.len() == 1 {
+                    self.visible_page_rect(size, scale, &target_rect, &screen_rect)
+                } else {
+                    None
+                };
+            }
+            None => {
+                draw_text_centered(
+                    context,
+                    "No Texture",
+                    screen_rect,
+                    self.fonts.large(),
+                    Color::BLACK,
+                );
+            }
+        }
+
+        if self.draw_next_volume(&screen_rect, context) {
+            return;
+        }
+
+        self.draw_bookmarks(&screen_rect, context);
+
+        if self.show_dots_timeout > 0.0 {
+            let (first, last) = self.shown_range(page);
+            let pages = if first == last {
+                format!("{}", first + 1)
+            } else {
+                format!("{}-{}", first + 1, last + 1)
+            };
+            let shifted = if self.view_mode == ViewMode::Spread && self.spread_shift {
+                ", shifted"
+            } else {
+                ""
+            };
+
+            let indicator = format!(
+                "{pages} / {} ({}{shifted})",
+                self.provider.page_count(),
+                self.page_fit.label()
+            );
+
+            self.draw_page_indicator(indicator.as_str(), &screen_rect, context);
+        }
+    }
+
+    //Keep the chunk index following the current page and find the page to start from,
+    //None while it's still unknown
+    pub(super) fn resolve_current_page(&mut self) -> Option<usize> {
+        //The chunk index is what gets saved as progress
+        let initial_chunk_index = self.current_chunk_index;
+        self.resolve_target_page();
+        if let Some(session) = self.session.as_mut() {
+            session.record_move(initial_chunk_index, self.current_chunk_index);
+        }
+
+        //Chunk markers don't apply to whole pages
+        self.current_chunk = None;
+
+        if self.current_page.is_none() {
+            //Start from the page of the last chunk read
+            let done = self.provider.done_processing();
+            self.current_page = match self.provider.get_chunk(self.current_chunk_index) {
+                Some(chunk) => Some(chunk.texture_index),
+                None if done => Some(0),
+                None => None,
+            };
+        }
+
+        self.current_page
+    }
+
+    //Page numbers at the bottom of the screen, shown like the chunk dots a while after moving
+    pub(super) fn draw_page_indicator(
+        &self,
+        text: &str,
+        screen_rect: &Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) {
+        draw_text_centered(
+            context,
+            text,
+            Rectangle::new(
+                screen_rect.x,
+                screen_rect.y + screen_rect.height - 24.0,
+                screen_rect.width,
+                24.0,
+            ),
+            self.fonts.default(),
+            Color::DARKGRAY,
+        );
+    }
+
+    //Where the page is drawn, keeping the scroll offset in bounds
+    fn page_target_rect(&mut self, size: Vector2, screen_rect: &Rectangle) -> Rectangle {
+        let real_size = page_screen_size(size, self.page_fit, screen_rect);
+        let scale = real_size.x / size.x;
+
+        //Scroll to the chunk the reader was on before switching from chunk mode
+        if let Some(focus) = self.page_focus.take() {
+            self.page_scroll = Vector2::new(
+                screen_rect.width / 2.0 - (focus.x + focus.width / 2.0) * scale,
+                -focus.y * scale,
+            );
+        }
+
+        let max_offset = Vector2::new(
+            screen_rect.width - real_size.x,
+            screen_rect.height - real_size.y,
+        );
+
+        //Axes that fit are centered, the others scroll
+        let x = if max_offset.x >= 0.0 {
+            self.page_scroll.x = 0.0;
+            max_offset.x / 2.0
+        } else {
+            self.page_scroll.x = self.page_scroll.x.clamp(max_offset.x, 0.0);
+            self.page_scroll.x
+        };
+
+        let y = if max_offset.y >= 0.0 {
+            self.page_scroll.y = 0.0;
+            max_offset.y / 2.0
+        } else {
+            self.page_scroll.y = self.page_scroll.y.clamp(max_offset.y, 0.0);
+            self.page_scroll.y
+        };
+
+        Rectangle::new(
+            screen_rect.x + x,
+            screen_rect.y + y,
+            real_size.x,
+            real_size.y,
+        )
+    }
+
+    //Part of the page on screen, in image coordinates. None while the page is seen from its start,
+    //so going back to chunk mode lands on the page's first chunk
+    fn visible_page_rect(
+        &self,
+        size: Vector2,
+        scale: f32,
+        target_rect: &Rectangle,
+        screen_rect: &Rectangle,
+    ) -> Option<Rectangle> {
+        let right_to_left = self.reading_direction == ReadingDirection::RightToLeft;
+        let overflow_x = target_rect.width > screen_rect.width;
+
+        //Half a pixel of slack for rounding
+        let at_top = target_rect.y >= screen_rect.y - 0.5;
+        let at_side = if !overflow_x {
+            true
+        } else if right_to_left {
+            target_rect.x + target_rect.width <= screen_rect.x + screen_rect.width + 0.5
+        } else {
+            target_rect.x >= screen_rect.x - 0.5
+        };
+
+        if at_top && at_side {
+            return None;
+        }
+
+        let visible = Rectangle::new(
+            (screen_rect.x - target_rect.x) / scale,
+            (screen_rect.y - target_rect.y) / scale,
+            screen_rect.width / scale,
+            screen_rect.height / scale,
+        );
+
+        visible.get_collision_rec(&Rectangle::new(0.0, 0.0, size.x, size.y))
+    }
+
+    //Show a page from its start, or from its end when moving backwards
+    fn set_page(&mut self, page: usize, from_end: bool) {
+        let right_to_left = self.reading_direction == ReadingDirection::RightToLeft;
+
+        //Offsets are clamped when drawn, the extremes land on the page's edges
+        let (start_x, end_x) = if right_to_left {
+            (-f32::MAX, 0.0)
+        } else {
+            (0.0, -f32::MAX)
+        };
+        self.page_scroll = if from_end {
+            Vector2::new(end_x, -f32::MAX)
+        } else {
+            Vector2::new(start_x, 0.0)
+        };
+
+        self.current_page = Some(page);
+        self.page_focus = None;
+        self.page_view_rect = None;
+    }
+
+    //Whether a page is a double spread on its own, pages not loaded yet are taken as single
+    fn is_wide_page(&self, page: usize) -> bool {
+        self.page_sizes
+            .get(&page)
+            .is_some_and(|size| size.x / size.y > WIDE_PAGE_RATIO)
+    }
+
+    //First and second page of the spread holding a page. Pages pair up from the start of the
+    //document, or from the last double spread before them; shifting shows the cover alone
+    fn spread_at(&self, page: usize) -> (usize, Option<usize>) {
+        if self.is_wide_page(page) || (self.spread_shift && page == 0) {
+            return (page, None);
+        }
+
+        let pairs_start = (0..page)
+            .rev()
+            .find(|it| self.is_wide_page(*it))
+            .map_or(self.spread_shift as usize, |it| it + 1);
+
+        if (page - pairs_start) % 2 == 1 {
+            return (page - 1, Some(page));
+        }
+
+        let next = page + 1;
+        if next < self.provider.page_count() && !self.is_wide_page(next) {
+            (page, Some(next))
+        } else {
+            (page, None)
+        }
+    }
+
+    //Pages drawn for the current page, from left to right
+    fn shown_pages(&self, page: usize) -> Vec<usize> {
+        if self.view_mode != ViewMode::Spread {
+            return vec![page];
+        }
+
+        let (first, second) = self.spread_at(page);
+        let mut pages: Vec<usize> = std::iter::once(first).chain(second).collect();
+
+        //Right to left books have their first page on the right
+        if self.reading_direction == ReadingDirection::RightToLeft {
+            pages.reverse();
+        }
+
+        pages
+    }
+
+    //Lowest and highest page shown
+    fn shown_range(&self, page: usize) -> (usize, usize) {
+        match self.view_mode {
+            ViewMode::Spread => {
+                let (first, second) = self.spread_at(page);
+                (first, second.unwrap_or(first))
+            }
+            _ => (page, page),
+        }
+    }
+
+    //Move by a page or spread forwards (positive offset) or backwards, offering the next volume
+    //past the last one
+    fn turn_page(&mut self, offset: i32) {
+        let page = match self.current_page {
+            Some(it) => it,
+            None => return,
+        };
+
+        self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+
+        let last_page = self.provider.page_count().saturating_sub(1);
+        let (first, last) = self.shown_range(page);
+        let new_page = if offset > 0 {
+            (last + 1).min(last_page)
+        } else {
+            first.saturating_sub(1)
+        };
+
+        if offset > 0 && last == last_page {
+            self.next_volume = self
+                .current_document_path
+                .as_ref()
+                .and_then(|path| self.db.next_volume(path));
+        } else if offset < 0 {
+            self.next_volume = None;
+        }
+
+        if new_page < first || new_page > last {
+            self.set_page(new_page, offset < 0);
+
+            //Resolved to the page's first chunk as it gets segmented
+            self.target_page = Some(self.shown_range(new_page).0);
+            self.target_rect = None;
+        }
+    }
+
+    //Handle user input in page mode
+    fn handle_page_input(
+        &mut self,
+        context: &mut RaylibDrawHandle,
+        screen_rect: &Rectangle,
+        page_size: Option<Vector2>,
+    ) {
+        self.zoom.update(context, screen_rect);
+
+        if self.handle_mode_keys(context) {
+            return;
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_F) {
+            self.page_fit = self.page_fit.next();
+            self.show_dots_timeout = DOTS_SHOW_TIMEOUT;
+            return;
+        }
+
+        if context.is_key_released(KeyboardKey::KEY_UP)
+            || context.is_key_released(KeyboardKey::KEY_DOWN)
+        {
+            self.can_scroll = true;
+        }
+
+        let real_size = page_size.map_or(Vector2::zero(), |size| {
+            page_screen_size(size, self.page_fit, screen_rect)
+        });
+        let overflow_y = real_size.y > screen_rect.height;
+        let overflow_x = real_size.x > screen_rect.width;
+
+        let step = screen_rect.height * SCROLL_STEP;
+        let scroll = if context.is_key_down(KeyboardKey::KEY_DOWN) {
+            -step
+        } else if context.is_key_down(KeyboardKey::KEY_UP) {
+            step
+        } else {
+            wheel_move(context) * SCROLL_STEP * (context.get_screen_height() as f32)
+        };
+
+        //Up and down turn the page once it's scrolled to its edge
+        let down_pressed = context.is_key_pressed(KeyboardKey::KEY_DOWN);
+        let up_pressed = context.is_key_pressed(KeyboardKey::KEY_UP);
+        let mut offset = 0;
+
+        if overflow_y {
+            let max_offset = screen_rect.height - real_size.y;
+            let at_top = self.page_scroll.y >= 0.0;
+            let at_bottom = self.page_scroll.y <= max_offset;
+
+            if self.can_scroll && down_pressed && at_bottom {
+                offset = 1;
+            } else if self.can_scroll && up_pressed && at_top {
+                offset = -1;
+            } else if self.can_scroll {
+                self.page_scroll.y = (self.page_scroll.y + scroll).clamp(max_offset, 0.0);
+            }
+        } else {
+            if overflow_x {
+                //Wide pages are scrolled sideways with the wheel
+                let max_offset = screen_rect.width - real_size.x;
+                self.page_scroll.x = (self.page_scroll.x
+                    + wheel_move(context) * SCROLL_STEP * (context.get_screen_width() as f32))
+                    .clamp(max_offset, 0.0);
+            }
+
+            if self.can_scroll && down_pressed {
+                offset = 1;
+            } else if self.can_scroll && up_pressed {
+                offset = -1;
+            }
+        }
+
+        if offset != 0 {
+            self.can_scroll = false;
+        }
+
+        //Right to left documents advance towards the left
+        let right_to_left = self.reading_direction == ReadingDirection::RightToLeft;
+        let (next_key, previous_key) = if right_to_left {
+            (KeyboardKey::KEY_LEFT, KeyboardKey::KEY_RIGHT)
+        } else {
+            (KeyboardKey::KEY_RIGHT, KeyboardKey::KEY_LEFT)
+        };
+
+        //Clicks on the next volume prompt and bookmarks are handled by their buttons,
+        //a click is only known once it can't be a double click anymore
+        let click = self.zoom.take_click().filter(|point| {
+            !((self.next_volume.is_some()
+                && next_volume_rect(screen_rect).check_collision_point_rec(*point))
+                || self.bookmarks_hovered(screen_rect, *point))
+        });
+
+        let clicked = click.is_some();
+        let left_half =
+            click.is_some_and(|point| point.x < screen_rect.x + screen_rect.width / 2.0);
+
+        if context.is_key_pressed(KeyboardKey::KEY_PAGE_DOWN)
+            || context.is_key_pressed(next_key)
+            || (clicked && left_half == right_to_left)
+        {
+            offset = 1;
+        } else if context.is_key_pressed(KeyboardKey::KEY_PAGE_UP)
+            || context.is_key_pressed(previous_key)
+            || (clicked && left_half != right_to_left)
+        {
+            offset = -1;
+        }
+
+        if offset != 0 {
+            self.turn_page(offset);
+        }
+    }
+}
+
+//Size a page takes on screen with the given fit
+fn page_screen_size(size: Vector2, fit: PageFit, screen_rect: &Rectangle) -> Vector2 {
+    let width_scale = screen_rect.width / size.x;
+    let height_scale = screen_rect.height / size.y;
+
+    let scale = match fit {
+        PageFit::Page => width_scale.min(height_scale),
+        PageFit::Width => width_scale,
+        PageFit::Height => height_scale,
+    };
+
+    Vector2::new(size.x * scale, size.y * scale)
+}
+
+//Size of pages side by side once scaled to the tallest one's height
+fn spread_size(sizes: &[Vector2]) -> Vector2 {
+    let height = sizes.iter().fold(0.0_f32, |height, it| height.max(it.y));
+    let width = sizes.iter().map(|it| it.x * height / it.y).sum();
+
+    Vector2::new(width, height)
+}
@@ -0,0 +1,265 @@
+use std::{collections::HashSet, ffi::CString};
+
+use raylib::prelude::*;
+
+use crate::structs::{ReadingSession, ReadingStatistics};
+
+use super::{draw_text_centered, get_time, Application, Screen};
+
+//Days shown in the time read chart, today included
+const CHART_DAYS: usize = 14;
+
+//Height of the time read chart
+const CHART_HEIGHT: f32 = 140.0;
+
+//Height of every series row
+const ROW_HEIGHT: f32 = 22.0;
+
+//Follows the reader through the open document, to be logged as a session once it's closed
+pub struct SessionTracker {
+    path: String,
+    started: u64,
+    chunks_advanced: usize,
+    pages: HashSet<usize>,
+}
+
+impl SessionTracker {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            started: get_time(),
+            chunks_advanced: 0,
+            pages: HashSet::new(),
+        }
+    }
+
+    //Count a move between chunks, only forward moves are progress
+    pub fn record_move(&mut self, from: usize, to: usize) {
+        self.chunks_advanced += to.saturating_sub(from);
+    }
+
+    pub fn record_page(&mut self, page: usize) {
+        self.pages.insert(page);
+    }
+
+    pub fn finish(self) -> ReadingSession {
+        ReadingSession {
+            path: self.path,
+            started: self.started,
+            ended: get_time(),
+            chunks_advanced: self.chunks_advanced,
+            pages_seen: self.pages.len(),
+        }
+    }
+}
+
+//"1h 05m", "12m" or "0m"
+fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+impl Application {
+    //Draw the statistics screen, returns true while it's shown
+    pub(super) fn statistics(
+        &mut self,
+        screen_rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) -> bool {
+        if context.gui_button(
+            Rectangle::new(screen_rect.x, screen_rect.y, 60.0, 24.0),
+            Some(CString::new("Back").unwrap().as_c_str()),
+        ) {
+            self.screen = Screen::Lobby;
+            self.statistics = None;
+            return true;
+        }
+
+        let statistics = match &self.statistics {
+            Some(it) => it.clone(),
+            None => {
+                let statistics = self.db.reading_statistics(CHART_DAYS);
+                self.statistics = Some(statistics.clone());
+                statistics
+            }
+        };
+
+        let totals = format!(
+            "{} read in {} sessions, {:.0}% of opened documents completed",
+            format_duration(statistics.seconds_read),
+            statistics.sessions,
+            statistics.completion * 100.0
+        );
+        draw_text_centered(
+            context,
+            totals.as_str(),
+            Rectangle::new(screen_rect.x, screen_rect.y, screen_rect.width, 24.0),
+            self.fonts.default(),
+            Color::BLACK,
+        );
+
+        let chart_rect = Rectangle::new(
+            screen_rect.x,
+            screen_rect.y + 40.0,
+            screen_rect.width,
+            CHART_HEIGHT,
+        );
+        self.draw_daily_chart(&statistics, chart_rect, context);
+
+        let list_rect = Rectangle::new(
+            screen_rect.x,
+            chart_rect.y + chart_rect.height + 20.0,
+            screen_rect.width,
+            screen_rect.height - chart_rect.height - 60.0,
+        );
+        self.draw_series_statistics(&statistics, list_rect, context);
+
+        true
+    }
+
+    //Bars with the time read each day
+    fn draw_daily_chart(
+        &self,
+        statistics: &ReadingStatistics,
+        rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) {
+        let most = statistics
+            .days
+            .iter()
+            .map(|(_, seconds)| *seconds)
+            .max()
+            .unwrap_or(0)
+            .max(60);
+
+        let slot_width = rect.width / statistics.days.len().max(1) as f32;
+        let bar_area = rect.height - 40.0;
+
+        for (index, (day, seconds)) in statistics.days.iter().enumerate() {
+            let x = rect.x + index as f32 * slot_width;
+            let height = bar_area * *seconds as f32 / most as f32;
+            let bar_y = rect.y + 20.0 + bar_area - height;
+
+            context.draw_rectangle_rec(
+                Rectangle::new(x + slot_width * 0.2, bar_y, slot_width * 0.6, height),
+                Color::SKYBLUE,
+            );
+
+            if *seconds > 0 {
+                draw_text_centered(
+                    context,
+                    format_duration(*seconds).as_str(),
+                    Rectangle::new(x, bar_y - 18.0, slot_width, 16.0),
+                    self.fonts.default(),
+                    Color::DARKGRAY,
+                );
+            }
+
+            //Only month and day, "YYYY-MM-DD"
+            draw_text_centered(
+                context,
+                day.get(5..).unwrap_or(day),
+                Rectangle::new(x, rect.y + rect.height - 18.0, slot_width, 16.0),
+                self.fonts.default(),
+                Color::BLACK,
+            );
+        }
+
+        context.draw_line_ex(
+            Vector2::new(rect.x, rect.y + 20.0 + bar_area),
+            Vector2::new(rect.x + rect.width, rect.y + 20.0 + bar_area),
+            1.0,
+            Color::GRAY,
+        );
+    }
+
+    //Time read and completion of every series that was opened, most read first
+    fn draw_series_statistics(
+        &mut self,
+        statistics: &ReadingStatistics,
+        rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) {
+        if statistics.series.is_empty() {
+            draw_text_centered(
+                context,
+                "Nothing read yet",
+                rect,
+                self.fonts.large(),
+                Color::BLACK,
+            );
+            return;
+        }
+
+        let content_rect = Rectangle::new(
+            rect.x,
+            rect.y,
+            rect.width - 14.0,
+            statistics.series.len() as f32 * ROW_HEIGHT,
+        );
+
+        let (view, scroll) = context.gui_scroll_panel(rect, content_rect, self.statistics_scroll);
+        self.statistics_scroll = scroll;
+
+        let bar_width = 120.0;
+        let time_width = 80.0;
+
+        unsafe {
+            raylib::ffi::BeginScissorMode(
+                view.x as i32,
+                view.y as i32,
+                view.width as i32,
+                view.height as i32,
+            );
+        }
+
+        for (index, series) in statistics.series.iter().enumerate() {
+            let y = view.y + scroll.y + index as f32 * ROW_HEIGHT;
+
+            if y + ROW_HEIGHT < view.y || y > view.y + view.height {
+                continue;
+            }
+
+            context.draw_text_ex(
+                self.fonts.default() as &Font,
+                series.name.as_str(),
+                Vector2::new(view.x + 6.0, y + 4.0),
+                self.fonts.default().baseSize as f32,
+                0.0,
+                Color::BLACK,
+            );
+
+            let right = view.x + view.width - 6.0;
+            draw_text_centered(
+                context,
+                format_duration(series.seconds_read).as_str(),
+                Rectangle::new(right - bar_width - time_width, y, time_width, ROW_HEIGHT),
+                self.fonts.default(),
+                Color::DARKGRAY,
+            );
+
+            let bar = Rectangle::new(right - bar_width, y + 5.0, bar_width, ROW_HEIGHT - 10.0);
+            context.draw_rectangle_rec(
+                Rectangle::new(bar.x, bar.y, bar.width * series.completion, bar.height),
+                Color::SKYBLUE,
+            );
+            context.draw_rectangle_lines_ex(bar, 1, Color::GRAY);
+            draw_text_centered(
+                context,
+                format!("{:.0}%", series.completion * 100.0).as_str(),
+                bar,
+                self.fonts.default(),
+                Color::BLACK,
+            );
+        }
+
+        unsafe {
+            raylib::ffi::EndScissorMode();
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::ffi::CString;
+
+use raylib::prelude::*;
+
+use crate::structs::Collection;
+
+use super::{buffer_text, draw_text_centered, Application};
+
+//Width of the editor panel
+const PANEL_WIDTH: f32 = 440.0;
+
+//Height of every control
+const ROW_HEIGHT: f32 = 24.0;
+
+//Space between controls
+const SPACING: f32 = 6.0;
+
+//Tags and collections of a single document, edited from its card
+pub struct TagEditor {
+    path: String,
+    title: String,
+    tags: Vec<String>,
+    //Tags of other documents, offered to be added with a click
+    other_tags: Vec<String>,
+    //Every collection, and whether the document is in it
+    collections: Vec<(Collection, bool)>,
+    //Text box contents, nul terminated
+    tag_buffer: [u8; 64],
+    tag_editing: bool,
+    collection_buffer: [u8; 64],
+    collection_editing: bool,
+}
+
+//A row of buttons wrapping at the panel's width, returns the index of the clicked one
+fn button_flow(
+    context: &mut RaylibDrawHandle,
+    labels: &[String],
+    font: &Font,
+    rect: Rectangle,
+    y: &mut f32,
+) -> Option<usize> {
+    let mut clicked = None;
+    let mut x = rect.x;
+
+    for (index, label) in labels.iter().enumerate() {
+        let width = measure_text_ex(font, label, font.baseSize as f32, 0.0).x + 20.0;
+
+        if x + width > rect.x + rect.width && x > rect.x {
+            x = rect.x;
+            *y += ROW_HEIGHT + SPACING;
+        }
+
+        if context.gui_button(
+            Rectangle::new(x, *y, width, ROW_HEIGHT),
+            Some(CString::new(label.as_str()).unwrap_or_default().as_c_str()),
+        ) {
+            clicked = Some(index);
+        }
+
+        x += width + SPACING;
+    }
+
+    if !labels.is_empty() {
+        *y += ROW_HEIGHT + SPACING;
+    }
+
+    clicked
+}
+
+impl Application {
+    pub(super) fn open_tag_editor(&mut self, path: &str, title: &str) {
+        let tags = self.db.tags_for(path);
+        let in_collections = self.db.collections_for(path);
+
+        self.tag_editor = Some(TagEditor {
+            path: path.to_string(),
+            title: title.to_string(),
+            other_tags: self
+                .db
+                .tags()
+                .into_iter()
+                .filter(|it| !tags.contains(it))
+                .collect(),
+            tags,
+            collections: self
+                .db
+                .collections()
+                .into_iter()
+                .map(|it| {
+                    let contains = in_collections.contains(&it);
+                    (it, contains)
+                })
+                .collect(),
+            tag_buffer: [0; 64],
+            tag_editing: false,
+            collection_buffer: [0; 64],
+            collection_editing: false,
+        });
+    }
+
+    //Draw the tag editor over the lobby or library, returns true while it's shown
+    pub(super) fn draw_tag_editor(
+        &mut self,
+        screen_rect: Rectangle,
+        context: &mut RaylibDrawHandle,
+    ) -> bool {
+        let mut editor = match self.tag_editor.take() {
+            Some(it) => it,
+            None => return false,
+        };
+
+        let panel = Rectangle::new(
+            screen_rect.x + (screen_rect.width - PANEL_WIDTH).max(0.0) / 2.0,
+            screen_rect.y,
+            PANEL_WIDTH.min(screen_rect.width),
+            screen_rect.height,
+        );
+        let inner = Rectangle::new(
+            panel.x + 10.0,
+            panel.y + 10.0,
+            panel.width - 20.0,
+            panel.height - 20.0,
+        );
+        let font = self.fonts.default();
+        let mut y = inner.y;
+
+        context.draw_rectangle_lines_ex(panel, 1, Color::LIGHTGRAY);
+        draw_text_centered(
+            context,
+            editor.title.as_str(),
+            Rectangle::new(inner.x, y, inner.width, ROW_HEIGHT),
+            self.fonts.bold(),
+            Color::BLACK,
+        );
+        y += ROW_HEIGHT + SPACING * 2.0;
+
+        //Current tags, clicking one removes it
+        context.draw_text_ex(
+            font as &Font,
+            "Tags (click to remove):",
+            Vector2::new(inner.x, y),
+            font.baseSize as f32,
+            0.0,
+            Color::DARKGRAY,
+        );
+        y += 20.0;
+
+        let labels: Vec<String> = editor.tags.iter().map(|it| format!("{it}  x")).collect();
+        if let Some(index) = button_flow(context, &labels, font, inner, &mut y) {
+            let tag = editor.tags.remove(index);
+            self.db.remove_tag(&editor.path, &tag);
+            editor.other_tags = self
+                .db
+                .tags()
+                .into_iter()
+                .filter(|it| !editor.tags.contains(it))
+                .collect();
+        }
+
+        let add_width = 110.0;
+        if context.gui_text_box(
+            Rectangle::new(inner.x, y, inner.width - add_width - SPACING, ROW_HEIGHT),
+            &mut editor.tag_buffer,
+            editor.tag_editing,
+        ) {
+            editor.tag_editing = !editor.tag_editing;
+        }
+
+        let add_tag = context.gui_button(
+            Rectangle::new(inner.x + inner.width - add_width, y, add_width, ROW_HEIGHT),
+            Some(CString::new("Add tag").unwrap().as_c_str()),
+        ) || (editor.tag_editing && context.is_key_pressed(KeyboardKey::KEY_ENTER));
+        y += ROW_HEIGHT + SPACING;
+
+        let mut new_tag = if add_tag {
+            let text = buffer_text(&editor.tag_buffer).trim().to_string();
+            editor.tag_buffer = [0; 64];
+            Some(text).filter(|it| !it.is_empty())
+        } else {
+            None
+        };
+
+        //Tags of other documents
+        if !editor.other_tags.is_empty() {
+            context.draw_text_ex(
+                font as &Font,
+                "Other tags (click to add):",
+                Vector2::new(inner.x, y),
+                font.baseSize as f32,
+                0.0,
+                Color::DARKGRAY,
+            );
+            y += 20.0;
+
+            if let Some(index) = button_flow(context, &editor.other_tags, font, inner, &mut y) {
+                new_tag = Some(editor.other_tags[index].clone());
+            }
+        }
+
+        if let Some(tag) = new_tag {
+            if !editor.tags.iter().any(|it| it.eq_ignore_ascii_case(&tag)) {
+                self.db.add_tag(&editor.path, &tag);
+                editor.tags = self.db.tags_for(&editor.path);
+                editor
+                    .other_tags
+                    .retain(|it| !it.eq_ignore_ascii_case(&tag));
+            }
+        }
+
+        y += SPACING * 2.0;
+
+        //Collections, toggled on and off
+        context.draw_text_ex(
+            font as &Font,
+            "Collections:",
+            Vector2::new(inner.x, y),
+            font.baseSize as f32,
+            0.0,
+            Color::DARKGRAY,
+        );
+        y += 20.0;
+
+        let delete_width = 60.0;
+        let mut deleted = None;
+
+        for (index, (collection, contains)) in editor.collections.iter_mut().enumerate() {
+            let toggled = context.gui_toggle(
+                Rectangle::new(inner.x, y, inner.width - delete_width - SPACING, ROW_HEIGHT),
+                Some(
+                    CString::new(collection.name.as_str())
+                        .unwrap_or_default()
+                        .as_c_str(),
+                ),
+                *contains,
+            );
+
+            if toggled != *contains {
+                *contains = toggled;
+                if toggled {
+                    self.db.add_to_collection(collection.id, &editor.path);
+                } else {
+                    self.db.remove_from_collection(collection.id, &editor.path);
+                }
+            }
+
+            if context.gui_button(
+                Rectangle::new(
+                    inner.x + inner.width - delete_width,
+                    y,
+                    delete_width,
+                    ROW_HEIGHT,
+                ),
+                Some(CString::new("Delete").unwrap().as_c_str()),
+            ) {
+                deleted = Some(index);
+            }
+
+            y += ROW_HEIGHT + SPACING;
+        }
+
+        if let Some(index) = deleted {
+            let (collection, _) = editor.collections.remove(index);
+            self.db.delete_collection(collection.id);
+        }
+
+        if context.gui_text_box(
+            Rectangle::new(inner.x, y, inner.width - add_width - SPACING, ROW_HEIGHT),
+            &mut editor.collection_buffer,
+            editor.collection_editing,
+        ) {
+            editor.collection_editing = !editor.collection_editing;
+        }
+
+        let create = context.gui_button(
+            Rectangle::new(inner.x + inner.width - add_width, y, add_width, ROW_HEIGHT),
+            Some(CString::new("New collection").unwrap().as_c_str()),
+        ) || (editor.collection_editing
+            && context.is_key_pressed(KeyboardKey::KEY_ENTER));
+
+        if create {
+            let name = buffer_text(&editor.collection_buffer).trim().to_string();
+            editor.collection_buffer = [0; 64];
+
+            //New collections start with the edited document in them
+            if !name.is_empty() {
+                if let Some(id) = self.db.create_collection(&name) {
+                    self.db.add_to_collection(id, &editor.path);
+                    editor.collections.retain(|(it, _)| it.id != id);
+                    editor.collections.push((Collection { id, name }, true));
+                }
+            }
+        }
+
+        let done = context.gui_button(
+            Rectangle::new(
+                inner.x + (inner.width - 120.0) / 2.0,
+                inner.y + inner.height - ROW_HEIGHT,
+                120.0,
+                ROW_HEIGHT,
+            ),
+            Some(CString::new("Done").unwrap().as_c_str()),
+        ) || (!editor.tag_editing
+            && !editor.collection_editing
+            && context.is_key_pressed(KeyboardKey::KEY_ESCAPE));
+
+        if done {
+            //Filters and cards may have changed
+            self.library.invalidate();
+            self.update_recents();
+        } else {
+            self.tag_editor = Some(editor);
+        }
+
+        true
+    }
+}
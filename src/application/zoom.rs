@@ -0,0 +1,301 @@
+use raylib::prelude::*;
+
+use crate::structs::ViewMode;
+
+//Zoom multiplier of each wheel step or key press
+const ZOOM_STEP: f32 = 1.25;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 8.0;
+
+//Seconds a click waits for a second one before it counts as a single click
+const DOUBLE_CLICK_TIME: f64 = 0.3;
+
+//Pixels the mouse moves with the button down before it's a drag instead of a click
+const DRAG_THRESHOLD: f32 = 5.0;
+
+//Side of the magnifier square and how much it enlarges what's under it
+const LOUPE_SIZE: f32 = 220.0;
+const LOUPE_ZOOM: f32 = 2.5;
+
+//Zoom and pan over the chunk or page shown, on top of the way it's fit into the screen
+pub struct Zoom {
+    //1 shows the chunk or page fit like without zoom
+    level: f32,
+    //Offset added once zoomed, in screen pixels
+    pan: Vector2,
+    //Level showing one image pixel per screen pixel, as of the last frame drawn
+    pixel_level: f32,
+    loupe: bool,
+    //Chunk or page zoomed into, the pan is reset when it changes
+    shown: Option<(ViewMode, usize)>,
+    //Press waiting to be known as a single or double click, with its time
+    pending_click: Option<(Vector2, f64)>,
+    //Where the mouse was pressed and the pan at that time
+    drag: Option<(Vector2, Vector2)>,
+    dragging: bool,
+    //Single click of this frame
+    click: Option<Vector2>,
+}
+
+impl Zoom {
+    pub fn new() -> Self {
+        Self {
+            level: 1.0,
+            pan: Vector2::zero(),
+            pixel_level: 1.0,
+            loupe: false,
+            shown: None,
+            pending_click: None,
+            drag: None,
+            dragging: false,
+            click: None,
+        }
+    }
+
+    //Back to fit, for a newly opened document
+    pub fn reset(&mut self) {
+        *self = Self {
+            loupe: self.loupe,
+            ..Self::new()
+        };
+    }
+
+    fn is_zoomed(&self) -> bool {
+        self.level != 1.0 || self.pan != Vector2::zero()
+    }
+
+    //Tell which chunk or page is on screen, moving to another one starts from its center
+    pub fn show(&mut self, mode: ViewMode, index: usize) {
+        if self.shown != Some((mode, index)) {
+            self.shown = Some((mode, index));
+            self.pan = Vector2::zero();
+        }
+    }
+
+    //Single click of this frame, delayed until it can't be a double click anymore
+    pub fn take_click(&mut self) -> Option<Vector2> {
+        self.click.take()
+    }
+
+    //Where to draw something fit into rect once zoomed, keeping the screen covered when larger.
+    //source_width is the image width drawn into rect, to know the level showing actual pixels
+    pub fn fit(
+        &mut self,
+        rect: Rectangle,
+        source_width: f32,
+        screen_rect: &Rectangle,
+    ) -> Rectangle {
+        self.pixel_level = source_width / rect.width;
+
+        if !self.is_zoomed() {
+            return rect;
+        }
+
+        let center = Vector2::new(
+            screen_rect.x + screen_rect.width / 2.0,
+            screen_rect.y + screen_rect.height / 2.0,
+        );
+
+        let width = rect.width * self.level;
+        let height = rect.height * self.level;
+
+        //Scaled around the center of the screen, then panned
+        let x = center.x + (rect.x - center.x) * self.level;
+        let y = center.y + (rect.y - center.y) * self.level;
+
+        let (x, pan_x) = clamp_axis(x, self.pan.x, width, screen_rect.x, screen_rect.width);
+        let (y, pan_y) = clamp_axis(y, self.pan.y, height, screen_rect.y, screen_rect.height);
+        self.pan = Vector2::new(pan_x, pan_y);
+
+        Rectangle::new(x, y, width, height)
+    }
+
+    //Change the level keeping the point under the given screen position in place
+    fn zoom_at(&mut self, level: f32, point: Vector2, screen_rect: &Rectangle) {
+        let level = level.clamp(MIN_ZOOM, MAX_ZOOM);
+        let center = Vector2::new(
+            screen_rect.x + screen_rect.width / 2.0,
+            screen_rect.y + screen_rect.height / 2.0,
+        );
+
+        let from_center = point - center;
+        self.pan = from_center - (from_center - self.pan) * (level / self.level);
+        self.level = level;
+    }
+
+    //Zoom keys and wheel, drag to pan and double click for actual pixels
+    pub fn update(&mut self, context: &mut RaylibDrawHandle, screen_rect: &Rectangle) {
+        let mouse = context.get_mouse_position();
+        let screen_center = Vector2::new(
+            screen_rect.x + screen_rect.width / 2.0,
+            screen_rect.y + screen_rect.height / 2.0,
+        );
+
+        if modifier_down(context) {
+            let wheel = context.get_mouse_wheel_move();
+            if wheel != 0.0 {
+                self.zoom_at(self.level * ZOOM_STEP.powf(wheel), mouse, screen_rect);
+            }
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_EQUAL)
+            || context.is_key_pressed(KeyboardKey::KEY_KP_ADD)
+        {
+            self.zoom_at(self.level * ZOOM_STEP, screen_center, screen_rect);
+        } else if context.is_key_pressed(KeyboardKey::KEY_MINUS)
+            || context.is_key_pressed(KeyboardKey::KEY_KP_SUBTRACT)
+        {
+            self.zoom_at(self.level / ZOOM_STEP, screen_center, screen_rect);
+        } else if context.is_key_pressed(KeyboardKey::KEY_ZERO)
+            || context.is_key_pressed(KeyboardKey::KEY_KP_0)
+        {
+            self.level = 1.0;
+            self.pan = Vector2::zero();
+        }
+
+        if context.is_key_pressed(KeyboardKey::KEY_L) {
+            self.loupe = !self.loupe;
+        }
+
+        let now = context.get_time();
+
+        if context.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON)
+            && screen_rect.check_collision_point_rec(mouse)
+        {
+            match self.pending_click.take() {
+                Some((point, time))
+                    if now - time <= DOUBLE_CLICK_TIME
+                        && point.distance_to(mouse) <= DRAG_THRESHOLD =>
+                {
+                    //Toggle between actual pixels and fit
+                    if (self.level - self.pixel_level).abs() < 0.01 {
+                        self.level = 1.0;
+                        self.pan = Vector2::zero();
+                    } else {
+                        self.zoom_at(self.pixel_level, mouse, screen_rect);
+                    }
+                }
+                _ => self.pending_click = Some((mouse, now)),
+            }
+
+            self.drag = Some((mouse, self.pan));
+        }
+
+        if let Some((origin, pan)) = self.drag {
+            if context.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) {
+                if self.dragging || origin.distance_to(mouse) > DRAG_THRESHOLD {
+                    //A drag is never a click
+                    self.dragging = true;
+                    self.pending_click = None;
+                    self.pan = pan + (mouse - origin);
+                    context.set_mouse_cursor(MouseCursor::MOUSE_CURSOR_RESIZE_ALL);
+                }
+            } else {
+                self.drag = None;
+                self.dragging = false;
+            }
+        }
+
+        if let Some((point, time)) = self.pending_click {
+            if now - time > DOUBLE_CLICK_TIME {
+                self.pending_click = None;
+                self.click = Some(point);
+            }
+        }
+    }
+
+    //Enlarged view of the image under the mouse, if the loupe is on and the mouse is over dest
+    pub fn draw_loupe(
+        &self,
+        context: &mut RaylibDrawHandle,
+        texture: &Texture2D,
+        source: Rectangle,
+        dest: Rectangle,
+    ) {
+        let mouse = context.get_mouse_position();
+        if !self.loupe || !dest.check_collision_point_rec(mouse) {
+            return;
+        }
+
+        //Screen pixels per image pixel, inside the loupe
+        let scale = dest.width / source.width * LOUPE_ZOOM;
+        let point = Vector2::new(
+            source.x + (mouse.x - dest.x) * source.width / dest.width,
+            source.y + (mouse.y - dest.y) * source.height / dest.height,
+        );
+
+        let half = LOUPE_SIZE / 2.0 / scale;
+        let wanted = Rectangle::new(point.x - half, point.y - half, half * 2.0, half * 2.0);
+        let frame = Rectangle::new(
+            mouse.x - LOUPE_SIZE / 2.0,
+            mouse.y - LOUPE_SIZE / 2.0,
+            LOUPE_SIZE,
+            LOUPE_SIZE,
+        );
+
+        context.draw_rectangle_rec(frame, Color::WHITE);
+
+        //Near the edges only part of the loupe has something to show
+        if let Some(visible) = wanted.get_collision_rec(&source) {
+            context.draw_texture_pro(
+                texture,
+                visible,
+                Rectangle::new(
+                    frame.x + (visible.x - wanted.x) * scale,
+                    frame.y + (visible.y - wanted.y) * scale,
+                    visible.width * scale,
+                    visible.height * scale,
+                ),
+                Vector2::zero(),
+                0f32,
+                Color::WHITE,
+            );
+        }
+
+        context.draw_rectangle_lines_ex(frame, 2, Color::DARKGRAY);
+    }
+
+    //Zoom level on the top left corner, while zoomed
+    pub fn draw_level(&self, context: &mut RaylibDrawHandle, screen_rect: &Rectangle, font: &Font) {
+        if self.level == 1.0 {
+            return;
+        }
+
+        context.draw_text_ex(
+            font,
+            format!("{:.0}%", self.level * 100.0).as_str(),
+            Vector2::new(screen_rect.x + 4.0, screen_rect.y + 4.0),
+            font.baseSize as f32,
+            0.0,
+            Color::DARKGRAY,
+        );
+    }
+}
+
+//Position along one axis and the pan leading to it: larger than the screen stays covering it,
+//smaller can't be panned
+fn clamp_axis(position: f32, pan: f32, size: f32, start: f32, length: f32) -> (f32, f32) {
+    if size > length {
+        let panned = (position + pan).clamp(start + length - size, start);
+        (panned, panned - position)
+    } else {
+        (position, 0.0)
+    }
+}
+
+//Control, or Command on macOS
+fn modifier_down(context: &RaylibDrawHandle) -> bool {
+    context.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+        || context.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL)
+        || context.is_key_down(KeyboardKey::KEY_LEFT_SUPER)
+        || context.is_key_down(KeyboardKey::KEY_RIGHT_SUPER)
+}
+
+//Mouse wheel movement for scrolling, none while it zooms
+pub fn wheel_move(context: &RaylibDrawHandle) -> f32 {
+    if modifier_down(context) {
+        0.0
+    } else {
+        context.get_mouse_wheel_move()
+    }
+}
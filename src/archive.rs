@@ -26,27 +26,34 @@ impl Archive {
         let c_path = CString::new(_path).unwrap();
         let path = Path::new(_path);
 
-        let stream = unsafe { ar_open_file(c_path.as_ptr()) };
+        let extension = match path.extension() {
+            Some(it) => it.to_string_lossy().to_lowercase(),
+            None => return Err(format!("No extension found for '{_path}'")),
+        };
 
-        let extension = path.extension().unwrap().to_str().unwrap();
+        let stream = unsafe { ar_open_file(c_path.as_ptr()) };
+        if stream.is_null() {
+            return Err(format!("Couldn't open '{_path}'"));
+        }
 
-        match extension {
-            "rar" | "cbr" => {
-                return Ok(Archive {
-                    handle: unsafe { ar_open_rar_archive(stream) },
-                    stream,
-                });
+        let handle = match extension.as_str() {
+            "rar" | "cbr" => unsafe { ar_open_rar_archive(stream) },
+            "zip" | "cbz" => unsafe { ar_open_zip_archive(stream, false) },
+            "7z" | "cb7" => unsafe { ar_open_7z_archive(stream) },
+            "tar" | "cbt" => unsafe { ar_open_tar_archive(stream) },
+            _ => {
+                unsafe { ar_close(stream) };
+                return Err(format!("Not supported extension: '{extension}'"));
             }
-            "zip" | "cbz" => {
-                return Ok(Archive {
-                    handle: unsafe { ar_open_zip_archive(stream, false) },
-                    stream,
-                });
-            }
-            _ => {}
+        };
+
+        //The stream may not be a valid archive even if the extension matches
+        if handle.is_null() {
+            unsafe { ar_close(stream) };
+            return Err(format!("Couldn't parse '{_path}' as a {extension} archive"));
         }
 
-        return Err(format!("Not supported extension: '{extension}'"));
+        Ok(Archive { handle, stream })
     }
 
     pub fn close(&self) {
@@ -84,25 +91,22 @@ impl Iterator for Archive {
     fn next(&mut self) -> Option<Self::Item> {
         if unsafe { ar_parse_entry(self.handle) } {
             let name_source = unsafe { ar_entry_get_name(self.handle) };
-            let name_len = unsafe { libc::strlen(name_source) };
-            let filetime = unsafe { ar_entry_get_filetime(self.handle) };
-            let name = unsafe { libc::malloc(name_len + 1) as *mut i8 };
-
-            unsafe {
-                libc::strcpy(name, name_source);
+            if name_source.is_null() {
+                return self.next();
             }
 
+            let filetime = unsafe { ar_entry_get_filetime(self.handle) };
+
             return Some(ArEntryInfo {
-                name: unsafe { CStr::from_ptr(name) }
-                    .to_str()
-                    .unwrap()
+                name: unsafe { CStr::from_ptr(name_source) }
+                    .to_string_lossy()
                     .to_string(),
                 filetime,
                 offset: unsafe { ar_entry_get_offset(self.handle) },
                 size: unsafe { ar_entry_get_size(self.handle).try_into().unwrap() },
             });
         } else {
-            self.close();
+            //The archive is kept open so entries can be read afterwards, owners must call close()
             return None;
         }
     }
@@ -0,0 +1,226 @@
+use std::{
+    cmp::max,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use raylib::prelude::Image;
+
+use crate::{
+    processing::get_chunks_from_image,
+    structs::{Chunk, DetectionParams, Message, PageRecord, ReadingDirection, ViewerCommand},
+};
+
+//How many chunks the worker tries to keep ready ahead of the reader
+const LOOKAHEAD_CHUNKS: usize = 10;
+
+//Pause between pages segmented past the lookahead, so finishing the document stays in the background
+const BACKGROUND_PAUSE: Duration = Duration::from_millis(50);
+
+//A list of pages that can be decoded independently from any thread
+pub trait PageSource: Send {
+    fn page_count(&self) -> usize;
+    fn load_page(&mut self, index: usize) -> Option<Image>;
+    //What the page was decoded from, to check cached chunks against
+    fn page_record(&self, index: usize) -> PageRecord;
+    //Create an independent source for the same document, to be moved into another thread
+    fn duplicate(&self) -> Result<Box<dyn PageSource>, String>;
+}
+
+//Viewer side of the background chunk detection, segments pages on its own thread
+pub struct ChunkWorker {
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+    //Chunks for every page, None if the page wasn't processed yet
+    pages: Vec<Option<Vec<Chunk>>>,
+    //Chunks received for the page currently being processed
+    pending_chunks: Vec<Chunk>,
+    //Chunks of the longest run of processed pages from the beginning of the document
+    chunks: Vec<Chunk>,
+    //How many pages are part of the chunks vec
+    processed_pages: usize,
+    //Work units requested from and delivered by the worker
+    requested: usize,
+    delivered: usize,
+}
+
+impl ChunkWorker {
+    pub fn spawn(
+        source: Box<dyn PageSource>,
+        cached_chunks: Option<Vec<Chunk>>,
+        direction: ReadingDirection,
+        params: DetectionParams,
+    ) -> Self {
+        let page_count = source.page_count();
+        let mut pages: Vec<Option<Vec<Chunk>>> = vec![None; page_count];
+
+        for chunk in cached_chunks.unwrap_or_default() {
+            if let Some(page) = pages.get_mut(chunk.texture_index) {
+                page.get_or_insert_with(Vec::new).push(chunk);
+            }
+        }
+
+        let skip: Vec<bool> = pages.iter().map(|page| page.is_some()).collect();
+
+        let (command_tx, command_rx) = channel::<Message>();
+        let (data_tx, data_rx) = channel::<Message>();
+
+        thread::spawn(move || worker_loop(source, skip, direction, params, command_rx, data_tx));
+
+        let mut worker = Self {
+            tx: command_tx,
+            rx: data_rx,
+            pages,
+            pending_chunks: Vec::new(),
+            chunks: Vec::new(),
+            processed_pages: 0,
+            requested: 0,
+            delivered: 0,
+        };
+        worker.extend_chunks();
+
+        worker
+    }
+
+    //Collect everything the worker sent since the last call, never blocks
+    pub fn poll(&mut self) {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Message::ChunkData(chunk)) => self.pending_chunks.push(chunk),
+                Ok(Message::PageProcessed(index)) => {
+                    let chunks = std::mem::take(&mut self.pending_chunks);
+                    self.delivered += max(chunks.len(), 1);
+                    if let Some(page) = self.pages.get_mut(index) {
+                        *page = Some(chunks);
+                    }
+                    self.extend_chunks();
+                }
+                Ok(Message::Command(_)) => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    //Ask the worker for enough chunks to reach the given index plus some lookahead
+    pub fn request_until(&mut self, index: usize) {
+        if self.done_processing() {
+            return;
+        }
+
+        let target = index + LOOKAHEAD_CHUNKS;
+        let expected = self.chunks.len() + self.requested.saturating_sub(self.delivered);
+
+        if target > expected {
+            let how_many = (target - expected) as u32;
+            if self
+                .tx
+                .send(Message::Command(ViewerCommand::MoreChunks { how_many }))
+                .is_ok()
+            {
+                self.requested += how_many as usize;
+            }
+        }
+    }
+
+    pub fn get_chunk(&self, index: usize) -> Option<&Chunk> {
+        self.chunks.get(index)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        if self.done_processing() {
+            return self.chunks.len();
+        }
+
+        //There is at least one more chunk coming
+        self.chunks.len() + 1
+    }
+
+    pub fn done_processing(&self) -> bool {
+        self.processed_pages == self.pages.len()
+    }
+
+    //Chunks of every processed page, even those after a page still being processed
+    pub fn known_chunks(&self) -> Vec<Chunk> {
+        self.pages.iter().flatten().flatten().copied().collect()
+    }
+
+    //Move newly completed pages into the chunk list, keeping page order
+    fn extend_chunks(&mut self) {
+        while let Some(Some(page)) = self.pages.get(self.processed_pages) {
+            self.chunks.extend_from_slice(page);
+            self.processed_pages += 1;
+        }
+    }
+}
+
+fn worker_loop(
+    mut source: Box<dyn PageSource>,
+    skip: Vec<bool>,
+    direction: ReadingDirection,
+    params: DetectionParams,
+    rx: Receiver<Message>,
+    tx: Sender<Message>,
+) {
+    let mut budget: usize = 0;
+    let mut next_page = 0;
+
+    loop {
+        while next_page < skip.len() && skip[next_page] {
+            next_page += 1;
+        }
+
+        let finished = next_page >= skip.len();
+
+        //Requested chunks are segmented right away, the rest of the document a page at a time
+        //until it's done, only then the worker waits for the viewer to go away
+        let message = if finished {
+            match rx.recv() {
+                Ok(it) => Some(it),
+                Err(_) => return,
+            }
+        } else if budget == 0 {
+            match rx.recv_timeout(BACKGROUND_PAUSE) {
+                Ok(it) => Some(it),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        } else {
+            match rx.try_recv() {
+                Ok(it) => Some(it),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        };
+
+        if let Some(Message::Command(ViewerCommand::MoreChunks { how_many })) = message {
+            budget += how_many as usize;
+            continue;
+        }
+
+        if finished {
+            budget = 0;
+            continue;
+        }
+
+        let mut chunks = match source.load_page(next_page) {
+            Some(mut image) => get_chunks_from_image(&mut image, direction, &params),
+            None => Vec::new(),
+        };
+
+        budget = budget.saturating_sub(max(chunks.len(), 1));
+
+        for mut chunk in chunks.drain(..) {
+            chunk.texture_index = next_page;
+            if tx.send(Message::ChunkData(chunk)).is_err() {
+                return;
+            }
+        }
+
+        if tx.send(Message::PageProcessed(next_page)).is_err() {
+            return;
+        }
+
+        next_page += 1;
+    }
+}
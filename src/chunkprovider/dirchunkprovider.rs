@@ -1,17 +1,23 @@
-use crate::processing::get_chunks_from_image;
+use crate::processing::{is_page_file, load_page_from_file, modified_time_of, sort_pages};
 use raylib::prelude::*;
-use std::{cmp::max, collections::HashMap, path::Path};
+use std::{collections::HashMap, path::Path};
 
-use crate::{structs::Chunk, traits::IChunkProvider};
+use crate::{
+    structs::{
+        Chunk, ChunkCache, DetectionParams, PageOrder, PageRecord, PageRotation, ReadingDirection,
+    },
+    traits::IChunkProvider,
+};
 
+use super::{chunkworker::PageSource, pageddocument::PagedDocument};
+
+#[derive(Default)]
 pub struct DirChunkProvider {
-    document_path: String,
-    files: Vec<String>,
-    chunk_index: HashMap<usize, Vec<usize>>,
-    chunks: Vec<Chunk>,
-    images: HashMap<usize, Image>,
-    image_loading_order: Vec<usize>,
-    last_queried_chunk: usize,
+    document: Option<PagedDocument>,
+    reading_direction: ReadingDirection,
+    detection_params: DetectionParams,
+    page_order: PageOrder,
+    page_rotations: HashMap<String, PageRotation>,
 }
 
 impl DirChunkProvider {
@@ -20,156 +26,141 @@ impl DirChunkProvider {
     }
 }
 
-impl IChunkProvider for DirChunkProvider {
-    fn get_chunk(&mut self, index: usize) -> Option<&crate::structs::Chunk> {
-        self.last_queried_chunk = index;
-        if index >= self.chunks.len() {
-            eprintln!("Queried chunk #{index} wich is out of bounds");
-            if self.chunk_index.len() == 0 {
-                self.get_image(0);
-            }
-            self.get_image(*self.chunk_index.keys().max().or(Some(&0)).unwrap() + 1 as usize);
-        }
+//Pages stored as image files inside a folder
+struct DirPageSource {
+    files: Vec<String>,
+    records: Vec<PageRecord>,
+}
 
-        self.chunks.get(index)
+impl PageSource for DirPageSource {
+    fn page_count(&self) -> usize {
+        self.files.len()
     }
 
-    fn chunk_count(&self) -> usize {
-        if self.done_processing() {
-            return self.chunks.len();
-        }
+    fn load_page(&mut self, index: usize) -> Option<Image> {
+        //Read through memory, so the extension is matched without caring about its case
+        load_page_from_file(self.files.get(index)?.as_str())
+    }
 
-        if self.files.len() > 0 {
-            return max(self.chunks.len() + 1, 1);
-        }
+    fn page_record(&self, index: usize) -> PageRecord {
+        self.records[index].clone()
+    }
 
-        return 0;
+    fn duplicate(&self) -> Result<Box<dyn PageSource>, String> {
+        Ok(Box::new(DirPageSource {
+            files: self.files.clone(),
+            records: self.records.clone(),
+        }))
     }
+}
 
-    fn done_processing(&self) -> bool {
-        if let Some(last_chunk) = self.chunks.last() {
-            return last_chunk.texture_index == self.files.len() - 1;
-        }
+impl IChunkProvider for DirChunkProvider {
+    fn get_chunk(&mut self, index: usize) -> Option<&Chunk> {
+        self.document.as_mut()?.get_chunk(index)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.chunk_count())
+    }
 
-        false
+    fn page_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.page_count())
+    }
+
+    fn done_processing(&self) -> bool {
+        self.document
+            .as_ref()
+            .is_some_and(|it| it.done_processing())
     }
 
     fn destroy(&self) {
         todo!()
     }
 
-    fn open(
-        self: &mut DirChunkProvider,
-        _path: &str,
-        cached_chunks: Option<Vec<Chunk>>,
-    ) -> Result<(), String> {
+    fn open(&mut self, path: &str, cache: Option<ChunkCache>) -> Result<(), String> {
+        self.document = Some(PagedDocument::new(
+            self.open_pages(path)?,
+            cache,
+            self.reading_direction,
+            self.detection_params,
+            &self.page_rotations,
+        )?);
+
+        //Preload first image
+        self.get_image(0);
+
+        Ok(())
+    }
+
+    fn open_pages(&self, _path: &str) -> Result<Box<dyn PageSource>, String> {
         let path = Path::new(_path);
         if path.exists() && path.is_dir() {
+            let mut files = Vec::new();
+
             if let Ok(dir) = path.read_dir() {
-                self.files = dir
+                files = dir
                     .map(|element| element.unwrap().path().to_str().unwrap().to_string())
-                    .filter(|element| {
-                        element.to_lowercase().ends_with(".jpg")
-                            || element.to_lowercase().ends_with(".png")
-                    })
+                    .filter(|element| is_page_file(element))
                     .collect();
             }
 
-            if let Some(chunks) = &cached_chunks {
-                self.chunks = chunks.clone();
-                let mut index: HashMap<usize, Vec<usize>> = HashMap::new();
-
-                for i in 0..self.chunks.len() {
-                    let c = self.chunks.get(i).unwrap();
-                    let t_index = c.texture_index;
-                    if !index.contains_key(&t_index) {
-                        index.insert(t_index, Vec::new());
-                    }
-
-                    index.get_mut(&t_index).unwrap().push(i);
-                }
-
-                self.chunk_index = index;
-            }
-
-            self.document_path = _path.to_string();
-
-            //Preload first image
-            self.get_image(0);
-
-            return Ok(());
+            //Size and modification time of every file, to know which pages changed
+            let mut pages: Vec<(String, PageRecord)> = files
+                .into_iter()
+                .map(|file| {
+                    let file_path = Path::new(&file);
+                    let record = PageRecord {
+                        name: file_path
+                            .file_name()
+                            .map(|it| it.to_string_lossy().to_string())
+                            .unwrap_or_default(),
+                        size: file_path.metadata().map_or(0, |it| it.len()),
+                        modified: modified_time_of(file_path),
+                        rotation: PageRotation::None,
+                    };
+                    (file, record)
+                })
+                .collect();
+
+            //read_dir returns files in no particular order
+            sort_pages(&mut pages, self.page_order, |(_, record)| {
+                (record.name.as_str(), record.modified)
+            });
+
+            let (files, records) = pages.into_iter().unzip();
+
+            return Ok(Box::new(DirPageSource { files, records }));
         }
 
         return Err("Error opening document".to_string());
     }
 
     fn get_image(&mut self, index: usize) -> Option<&raylib::texture::Image> {
-        eprintln!("Getting image {index}");
-
-        if index >= self.files.len() {
-            eprintln!("Index out of range!");
-            return None;
-        }
-
-        if self.images.contains_key(&index) {
-            eprintln!("Returning image from hash");
-            return self.images.get(&index);
-        }
-
-        eprintln!("Image {} not found, fetching...", index);
-
-        let mut image = match Image::load_image(self.files[index].as_str()) {
-            Ok(it) => it,
-            Err(error) => {
-                log::error!("Error loading image: {error}");
-                return None;
-            }
-        };
-
-        self.images.insert(index, image.clone());
-
-        if index == self.chunk_index.len() {
-            let mut image_chunks = get_chunks_from_image(&mut image);
-
-            for mut item in image_chunks.iter_mut() {
-                item.texture_index = index
-            }
-
-            let mut index_vec = Vec::new();
-            let start_index = self.chunks.len();
-            for i in 0..image_chunks.len() {
-                index_vec.push(start_index + i);
-                self.chunks.push(image_chunks.remove(0));
-            }
+        self.document.as_mut()?.get_image(index)
+    }
 
-            self.chunk_index.insert(index, index_vec);
-        }
+    fn chunk_cache(&self) -> Option<ChunkCache> {
+        Some(self.document.as_ref()?.chunk_cache())
+    }
 
-        self.image_loading_order.push(index);
+    fn set_reading_direction(&mut self, direction: ReadingDirection) {
+        self.reading_direction = direction;
+    }
 
-        if self.images.len() > 3 {
-            let next_to_remove = self.image_loading_order.remove(0);
-            eprintln!("Removing key {}", next_to_remove);
+    fn set_detection_params(&mut self, params: DetectionParams) {
+        self.detection_params = params;
+    }
 
-            self.images.remove(&next_to_remove);
-            eprintln!("Now hash's len is {}", self.images.len())
-        }
+    fn set_page_order(&mut self, order: PageOrder) {
+        self.page_order = order;
+    }
 
-        self.images.get(&index)
+    fn set_page_rotations(&mut self, rotations: HashMap<String, PageRotation>) {
+        self.page_rotations = rotations;
     }
 
     fn unload(&mut self) {
-        if self.document_path.is_empty() || !Path::new(self.document_path.as_str()).exists() {
-            eprintln!("Path is empty!");
-            return;
-        }
-
-        self.files.clear();
-        self.image_loading_order.clear();
-        self.images.clear();
-        self.chunks.clear();
-        self.chunk_index.clear();
-        self.document_path = String::new();
+        self.document = None;
     }
 
     fn can_open(&self, document_path: &str) -> bool {
@@ -177,17 +168,3 @@ impl IChunkProvider for DirChunkProvider {
         return path.exists() && path.is_dir();
     }
 }
-
-impl Default for DirChunkProvider {
-    fn default() -> Self {
-        Self {
-            files: Vec::new(),
-            images: HashMap::new(),
-            chunks: Vec::new(),
-            image_loading_order: Vec::new(),
-            chunk_index: HashMap::new(),
-            document_path: String::new(),
-            last_queried_chunk: 0,
-        }
-    }
-}
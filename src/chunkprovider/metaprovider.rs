@@ -1,8 +1,17 @@
+use std::collections::HashMap;
+
 use raylib::prelude::Image;
 
-use crate::{structs::Chunk, traits::IChunkProvider};
+use crate::{
+    structs::{Chunk, ChunkCache, DetectionParams, PageOrder, PageRotation, ReadingDirection},
+    traits::IChunkProvider,
+};
 
-use super::dirchunkprovider::DirChunkProvider;
+#[cfg(feature = "unarr")]
+use super::unarrchunkprovider::UnarrChunkProvider;
+use super::{
+    chunkworker::PageSource, dirchunkprovider::DirChunkProvider, zipchunkprovider::ZipChunkProvider,
+};
 
 pub struct MetaProvider {
     providers: Vec<Box<dyn IChunkProvider>>,
@@ -11,15 +20,20 @@ pub struct MetaProvider {
 
 impl MetaProvider {
     pub fn new() -> Self {
-        let _self = Self {
-            current_provider_index: 0,
-            providers: Vec::from([
-                Box::new(DirChunkProvider::new()) as Box<dyn IChunkProvider>,
-                // Box::new(UnarrChunkProvider::new()) as Box<dyn IChunkProvider>,
-            ]),
-        };
+        #[allow(unused_mut)]
+        let mut providers = Vec::from([
+            Box::new(DirChunkProvider::new()) as Box<dyn IChunkProvider>,
+            //Native ZIP support takes precedence over unarr for CBZ files
+            Box::new(ZipChunkProvider::new()) as Box<dyn IChunkProvider>,
+        ]);
+
+        #[cfg(feature = "unarr")]
+        providers.push(Box::new(UnarrChunkProvider::new()) as Box<dyn IChunkProvider>);
 
-        _self
+        Self {
+            current_provider_index: 0,
+            providers,
+        }
     }
 
     pub fn current_provider(&self) -> &Box<dyn IChunkProvider> {
@@ -40,6 +54,10 @@ impl IChunkProvider for MetaProvider {
         self.current_provider().chunk_count()
     }
 
+    fn page_count(&self) -> usize {
+        self.current_provider().page_count()
+    }
+
     fn done_processing(&self) -> bool {
         self.current_provider().done_processing()
     }
@@ -48,7 +66,7 @@ impl IChunkProvider for MetaProvider {
         self.current_provider().destroy()
     }
 
-    fn open(&mut self, path: &str, cached_chunks: Option<Vec<Chunk>>) -> Result<(), String> {
+    fn open(&mut self, path: &str, cache: Option<ChunkCache>) -> Result<(), String> {
         let mut index = 0;
 
         //Get a provider that can handle this file format
@@ -65,13 +83,48 @@ impl IChunkProvider for MetaProvider {
             return Err("No provider found for this document!".to_string());
         }
 
-        self.current_provider_mut().open(path, cached_chunks)
+        self.current_provider_mut().open(path, cache)
+    }
+
+    fn open_pages(&self, path: &str) -> Result<Box<dyn PageSource>, String> {
+        match self.providers.iter().find(|it| it.can_open(path)) {
+            Some(provider) => provider.open_pages(path),
+            None => Err("No provider found for this document!".to_string()),
+        }
     }
 
     fn get_image(&mut self, index: usize) -> Option<&Image> {
         self.current_provider_mut().get_image(index)
     }
 
+    fn chunk_cache(&self) -> Option<ChunkCache> {
+        self.current_provider().chunk_cache()
+    }
+
+    fn set_reading_direction(&mut self, direction: ReadingDirection) {
+        for provider in self.providers.iter_mut() {
+            provider.set_reading_direction(direction);
+        }
+    }
+
+    fn set_detection_params(&mut self, params: DetectionParams) {
+        for provider in self.providers.iter_mut() {
+            provider.set_detection_params(params);
+        }
+    }
+
+    fn set_page_order(&mut self, order: PageOrder) {
+        for provider in self.providers.iter_mut() {
+            provider.set_page_order(order);
+        }
+    }
+
+    fn set_page_rotations(&mut self, rotations: HashMap<String, PageRotation>) {
+        for provider in self.providers.iter_mut() {
+            provider.set_page_rotations(rotations.clone());
+        }
+    }
+
     fn unload(&mut self) {
         self.current_provider_mut().unload();
     }
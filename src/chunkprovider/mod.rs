@@ -1,3 +1,7 @@
+pub mod chunkworker;
 pub mod dirchunkprovider;
 pub mod metaprovider;
+pub mod pageddocument;
+#[cfg(feature = "unarr")]
 pub mod unarrchunkprovider;
+pub mod zipchunkprovider;
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use raylib::prelude::Image;
+
+use crate::structs::{
+    Chunk, ChunkCache, DetectionParams, PageRecord, PageRotation, ReadingDirection,
+};
+
+use super::chunkworker::{ChunkWorker, PageSource};
+
+//How many decoded images are kept around for the viewer
+const MAX_CACHED_IMAGES: usize = 3;
+
+//An opened document: pages decoded on demand for display, chunks detected in the background
+pub struct PagedDocument {
+    source: Box<dyn PageSource>,
+    worker: ChunkWorker,
+    images: HashMap<usize, Image>,
+    image_loading_order: Vec<usize>,
+    records: Vec<PageRecord>,
+}
+
+impl PagedDocument {
+    pub fn new(
+        source: Box<dyn PageSource>,
+        cache: Option<ChunkCache>,
+        direction: ReadingDirection,
+        params: DetectionParams,
+        rotations: &HashMap<String, PageRotation>,
+    ) -> Result<Self, String> {
+        if source.page_count() == 0 {
+            return Err("No pages found in this document".to_string());
+        }
+
+        let source = RotatedPageSource::wrap(source, rotations);
+
+        let records: Vec<PageRecord> = (0..source.page_count())
+            .map(|index| source.page_record(index))
+            .collect();
+
+        let cached_chunks = cache.map(|it| remap_cached_chunks(it, &records));
+
+        let worker = ChunkWorker::spawn(source.duplicate()?, cached_chunks, direction, params);
+
+        Ok(Self {
+            source,
+            worker,
+            images: HashMap::new(),
+            image_loading_order: Vec::new(),
+            records,
+        })
+    }
+
+    pub fn get_chunk(&mut self, index: usize) -> Option<&Chunk> {
+        self.worker.poll();
+        self.worker.request_until(index);
+        self.worker.get_chunk(index)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.worker.chunk_count()
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.source.page_count()
+    }
+
+    pub fn done_processing(&self) -> bool {
+        self.worker.done_processing()
+    }
+
+    pub fn chunk_cache(&self) -> ChunkCache {
+        ChunkCache {
+            chunks: self.worker.known_chunks(),
+            pages: self.records.clone(),
+        }
+    }
+
+    pub fn get_image(&mut self, index: usize) -> Option<&Image> {
+        if index >= self.source.page_count() {
+            eprintln!("Index out of range!");
+            return None;
+        }
+
+        if !self.images.contains_key(&index) {
+            let image = self.source.load_page(index)?;
+            self.images.insert(index, image);
+            self.image_loading_order.push(index);
+
+            if self.images.len() > MAX_CACHED_IMAGES {
+                let next_to_remove = self.image_loading_order.remove(0);
+                self.images.remove(&next_to_remove);
+            }
+        }
+
+        self.images.get(&index)
+    }
+}
+
+//Pages of another source turned as the reader asked, so chunks are detected on what is shown
+struct RotatedPageSource {
+    source: Box<dyn PageSource>,
+    rotations: Vec<PageRotation>,
+}
+
+impl RotatedPageSource {
+    //Left as is when no page is turned
+    fn wrap(
+        source: Box<dyn PageSource>,
+        rotations: &HashMap<String, PageRotation>,
+    ) -> Box<dyn PageSource> {
+        if rotations.is_empty() {
+            return source;
+        }
+
+        let rotations = (0..source.page_count())
+            .map(|index| {
+                rotations
+                    .get(&source.page_record(index).name)
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        Box::new(Self { source, rotations })
+    }
+}
+
+impl PageSource for RotatedPageSource {
+    fn page_count(&self) -> usize {
+        self.source.page_count()
+    }
+
+    fn load_page(&mut self, index: usize) -> Option<Image> {
+        let mut image = self.source.load_page(index)?;
+        self.rotations[index].apply(&mut image);
+        Some(image)
+    }
+
+    fn page_record(&self, index: usize) -> PageRecord {
+        PageRecord {
+            rotation: self.rotations[index],
+            ..self.source.page_record(index)
+        }
+    }
+
+    fn duplicate(&self) -> Result<Box<dyn PageSource>, String> {
+        Ok(Box::new(Self {
+            source: self.source.duplicate()?,
+            rotations: self.rotations.clone(),
+        }))
+    }
+}
+
+//Move cached chunks to the current index of their page, dropping those of pages that changed or were turned
+fn remap_cached_chunks(cache: ChunkCache, records: &[PageRecord]) -> Vec<Chunk> {
+    let current_indexes: HashMap<&str, usize> = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| (record.name.as_str(), index))
+        .collect();
+
+    //Old page index to new page index, for pages that are still the same
+    let new_indexes: Vec<Option<usize>> = cache
+        .pages
+        .iter()
+        .map(|record| {
+            current_indexes
+                .get(record.name.as_str())
+                .copied()
+                .filter(|&index| records[index] == *record)
+        })
+        .collect();
+
+    cache
+        .chunks
+        .into_iter()
+        .filter_map(|mut chunk| {
+            chunk.texture_index = (*new_indexes.get(chunk.texture_index)?)?;
+            Some(chunk)
+        })
+        .collect()
+}
@@ -1,148 +1,198 @@
-// #[derive(Debug)]
-// #[allow(dead_code)]
-// pub struct ChunkProvider<'a> {
-//     pub archive: Archive,
-//     pub entries: Vec<ArEntryInfo<'a>>,
-//     chunks: Vec<Chunk>,
-//     tx: Sender<Message>,
-//     rx: Receiver<Message>,
-// }
-
-// impl<'a> ChunkProvider<'a> {
-//     #[allow(unused)]
-//     pub fn new(_path: String) -> Self {
-//         todo!()
-//         // let archive = Archive::new(&path);
-
-//         // let (mtx, mrx): (Sender<Message<'a>>, Receiver<Message<'a>>) = channel();
-//         // let (_stx, srx): (Sender<Message<'a>>, Receiver<Message<'a>>) = channel();
-
-//         // std::thread::spawn(move || {
-//         //     let (_rl, _context) = init().build();
-//         //     loop {
-//         //         let mut chunk_query_count = 0;
-
-//         //         match srx.recv() {
-//         //             Ok(msg) => match msg {
-//         //                 Message::Command(cmd) => match cmd {
-//         //                     ViewerCommand::MoreChunks { how_many } => chunk_query_count = how_many,
-//         //                 },
-//         //                 Message::ChunkData(_) => {}
-//         //             },
-//         //             Err(_) => {
-//         //                 return;
-//         //             }
-//         //         }
-
-//         //         while chunk_query_count > 0 {}
-//         //     }
-//         // });
-
-//         // return ChunkProvider {
-//         //     archive,
-//         //     entries: archive.collect(),
-//         //     chunks: Vec::new(),
-//         //     tx: mtx,
-//         //     rx: mrx,
-//         // };
-//     }
-// }
-
-// impl IChunkProvider for ChunkProvider<'_> {
-//     fn get_chunk(&self, _: usize) -> Option<Chunk> {
-//         None
-//     }
-
-//     fn chunk_count(&self) -> usize {
-//         return self.chunks.len();
-//     }
-
-//     fn done_processing(&self) -> bool {
-//         todo!()
-//     }
-
-//     fn destroy(&self) {
-//         todo!("Implement this!");
-//     }
-
-//     fn open(_path: &str) -> Self {
-//         todo!()
-//     }
-
-//     fn get_image(&self, index: usize) -> Option<Image> {
-//         None
-//     }
-
-//     fn new() -> Self {
-//         todo!()
-//     }
-// }
-
-// pub struct DummyChunkProvider {
-//     chunk_count: usize,
-//     done: bool,
-//     _image: Option<Image>,
-//     sizes: Vec<(i32, i32)>,
-// }
-
-// impl IChunkProvider for DummyChunkProvider {
-//     fn get_chunk(&mut self, index: usize) -> Option<&Chunk> {
-//         let chunk=Chunk {
-//             rect: Rectangle {
-//                 x: 5.0,
-//                 y: 5.0,
-//                 width: self.sizes[index].0 as f32,
-//                 height: self.sizes[index].1 as f32,
-//             },
-
-//             texture_index: 0,
-//             status: crate::structs::ChunkStatus::Ready,
-//         };
-//         Some(&chunk)
-//     }
-
-//     fn chunk_count(&self) -> usize {
-//         self.chunk_count
-//     }
-
-//     fn done_processing(&self) -> bool {
-//         return self.done;
-//     }
-
-//     fn destroy(&self) {}
-
-//     fn open(&mut self, _path: &str) -> bool {
-//         return true;
-//     }
-
-//     fn get_image(&mut self, _index: usize) -> Option<&Image> {
-//         Some(&self._image.as_ref().unwrap())
-//     }
-
-//     fn new() -> Self {
-//         const CHUNK_COUNT: usize = 10;
-//         let image = Image::gen_image_checked(800, 600, 10, 10, Color::BLACK, Color::WHITE);
-//         let mut sizes = Vec::new();
-
-//         for _ in 0..CHUNK_COUNT {
-//             let w = get_random_value(0, image.width);
-//             let h = get_random_value(0, image.height);
-//             sizes.push((w, h));
-//         }
-
-//         Self {
-//             chunk_count: CHUNK_COUNT,
-//             done: false,
-//             _image: Some(image),
-//             sizes: sizes,
-//         }
-//     }
-
-//     fn get_metadata(_: &str) -> Option<ComicMetadata> {
-//         Some(ComicMetadata {
-//             title: "Some Comic".to_string(),
-//             chunk_count: 10,
-//             last_seen_chunk: 2,
-//         })
-//     }
-// }
+use crate::{
+    archive::{ArEntryInfo, Archive},
+    processing::{extension_of, is_page_file, load_page_from_memory, sort_pages},
+};
+use raylib::prelude::*;
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    structs::{
+        Chunk, ChunkCache, DetectionParams, PageOrder, PageRecord, PageRotation, ReadingDirection,
+    },
+    traits::IChunkProvider,
+};
+
+use super::{chunkworker::PageSource, pageddocument::PagedDocument};
+
+//Archive formats handled by unarr
+const ARCHIVE_EXTENSIONS: [&str; 8] = ["cbr", "rar", "cbz", "zip", "cb7", "7z", "cbt", "tar"];
+
+#[derive(Default)]
+pub struct UnarrChunkProvider {
+    document: Option<PagedDocument>,
+    reading_direction: ReadingDirection,
+    detection_params: DetectionParams,
+    page_order: PageOrder,
+    page_rotations: HashMap<String, PageRotation>,
+}
+
+impl UnarrChunkProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+//Pages stored as entries of any archive unarr can read
+struct UnarrPageSource {
+    path: String,
+    //Opened lazily, by the thread that owns this source
+    archive: Option<Archive>,
+    entries: Vec<ArEntryInfo>,
+}
+
+//The archive handle is never shared, it is only used by whoever owns the source
+unsafe impl Send for UnarrPageSource {}
+
+impl PageSource for UnarrPageSource {
+    fn page_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn load_page(&mut self, index: usize) -> Option<Image> {
+        let entry = self.entries.get(index)?;
+
+        if self.archive.is_none() {
+            match Archive::new(&self.path) {
+                Ok(it) => self.archive = Some(it),
+                Err(error) => {
+                    log::error!("Error opening archive: {error}");
+                    return None;
+                }
+            }
+        }
+
+        match self.archive.as_ref()?.read(entry.offset, entry.size) {
+            Ok(data) => load_page_from_memory(&entry.name, &data),
+            Err(error) => {
+                log::error!("Error reading entry '{}': {error}", entry.name);
+                None
+            }
+        }
+    }
+
+    fn page_record(&self, index: usize) -> PageRecord {
+        let entry = &self.entries[index];
+
+        PageRecord {
+            name: entry.name.clone(),
+            size: entry.size as u64,
+            //Raw entry timestamp, only compared for equality
+            modified: entry.filetime as u64,
+            rotation: PageRotation::None,
+        }
+    }
+
+    fn duplicate(&self) -> Result<Box<dyn PageSource>, String> {
+        Ok(Box::new(UnarrPageSource {
+            path: self.path.clone(),
+            archive: None,
+            entries: self.entries.clone(),
+        }))
+    }
+}
+
+impl Drop for UnarrPageSource {
+    fn drop(&mut self) {
+        if let Some(archive) = self.archive.take() {
+            archive.close();
+        }
+    }
+}
+
+impl IChunkProvider for UnarrChunkProvider {
+    fn get_chunk(&mut self, index: usize) -> Option<&Chunk> {
+        self.document.as_mut()?.get_chunk(index)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.chunk_count())
+    }
+
+    fn page_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.page_count())
+    }
+
+    fn done_processing(&self) -> bool {
+        self.document
+            .as_ref()
+            .is_some_and(|it| it.done_processing())
+    }
+
+    fn destroy(&self) {
+        //The archive handle is released on unload
+    }
+
+    fn open(&mut self, path: &str, cache: Option<ChunkCache>) -> Result<(), String> {
+        self.document = Some(PagedDocument::new(
+            self.open_pages(path)?,
+            cache,
+            self.reading_direction,
+            self.detection_params,
+            &self.page_rotations,
+        )?);
+
+        //Preload first image
+        self.get_image(0);
+
+        Ok(())
+    }
+
+    fn open_pages(&self, path: &str) -> Result<Box<dyn PageSource>, String> {
+        let archive = Archive::new(path)?;
+
+        //Only keep entries that can be decoded as pages
+        let mut entries = archive
+            .filter(|entry| is_page_file(&entry.name))
+            .collect::<Vec<ArEntryInfo>>();
+        sort_pages(&mut entries, self.page_order, |entry| {
+            (entry.name.as_str(), entry.filetime as u64)
+        });
+
+        Ok(Box::new(UnarrPageSource {
+            path: path.to_string(),
+            archive: Some(archive),
+            entries,
+        }))
+    }
+
+    fn get_image(&mut self, index: usize) -> Option<&Image> {
+        self.document.as_mut()?.get_image(index)
+    }
+
+    fn chunk_cache(&self) -> Option<ChunkCache> {
+        Some(self.document.as_ref()?.chunk_cache())
+    }
+
+    fn set_reading_direction(&mut self, direction: ReadingDirection) {
+        self.reading_direction = direction;
+    }
+
+    fn set_detection_params(&mut self, params: DetectionParams) {
+        self.detection_params = params;
+    }
+
+    fn set_page_order(&mut self, order: PageOrder) {
+        self.page_order = order;
+    }
+
+    fn set_page_rotations(&mut self, rotations: HashMap<String, PageRotation>) {
+        self.page_rotations = rotations;
+    }
+
+    fn unload(&mut self) {
+        self.document = None;
+    }
+
+    fn can_open(&self, document_path: &str) -> bool {
+        let path = Path::new(document_path);
+
+        if !path.is_file() {
+            return false;
+        }
+
+        match extension_of(document_path) {
+            Some(extension) => ARCHIVE_EXTENSIONS.contains(&extension.as_str()),
+            None => false,
+        }
+    }
+}
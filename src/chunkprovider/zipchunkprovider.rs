@@ -0,0 +1,180 @@
+use crate::{
+    processing::{extension_of, is_page_file, load_page_from_memory, sort_pages},
+    ziparchive::{ZipArchive, ZipEntryInfo},
+};
+use raylib::prelude::*;
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    structs::{
+        Chunk, ChunkCache, DetectionParams, PageOrder, PageRecord, PageRotation, ReadingDirection,
+    },
+    traits::IChunkProvider,
+};
+
+use super::{chunkworker::PageSource, pageddocument::PagedDocument};
+
+//Archive formats handled natively
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["cbz", "zip"];
+
+#[derive(Default)]
+pub struct ZipChunkProvider {
+    document: Option<PagedDocument>,
+    reading_direction: ReadingDirection,
+    detection_params: DetectionParams,
+    page_order: PageOrder,
+    page_rotations: HashMap<String, PageRotation>,
+}
+
+impl ZipChunkProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+//Pages stored as entries of a ZIP archive
+struct ZipPageSource {
+    path: String,
+    archive: ZipArchive,
+    entries: Vec<ZipEntryInfo>,
+}
+
+impl PageSource for ZipPageSource {
+    fn page_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn load_page(&mut self, index: usize) -> Option<Image> {
+        let entry = self.entries.get(index)?;
+
+        match self.archive.read(entry) {
+            Ok(data) => load_page_from_memory(&entry.name, &data),
+            Err(error) => {
+                log::error!("Error reading entry '{}': {error}", entry.name);
+                None
+            }
+        }
+    }
+
+    fn page_record(&self, index: usize) -> PageRecord {
+        let entry = &self.entries[index];
+
+        PageRecord {
+            name: entry.name.clone(),
+            size: entry.size,
+            //Each entry's own timestamp, so editing some pages of the archive keeps the others' chunks
+            modified: entry.modified,
+            rotation: PageRotation::None,
+        }
+    }
+
+    fn duplicate(&self) -> Result<Box<dyn PageSource>, String> {
+        //Each source needs its own file handle, as reads seek around the archive
+        Ok(Box::new(ZipPageSource {
+            path: self.path.clone(),
+            archive: ZipArchive::new(&self.path)?,
+            entries: self.entries.clone(),
+        }))
+    }
+}
+
+impl IChunkProvider for ZipChunkProvider {
+    fn get_chunk(&mut self, index: usize) -> Option<&Chunk> {
+        self.document.as_mut()?.get_chunk(index)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.chunk_count())
+    }
+
+    fn page_count(&self) -> usize {
+        self.document.as_ref().map_or(0, |it| it.page_count())
+    }
+
+    fn done_processing(&self) -> bool {
+        self.document
+            .as_ref()
+            .is_some_and(|it| it.done_processing())
+    }
+
+    fn destroy(&self) {
+        //The archive's file is closed on unload
+    }
+
+    fn open(&mut self, path: &str, cache: Option<ChunkCache>) -> Result<(), String> {
+        self.document = Some(PagedDocument::new(
+            self.open_pages(path)?,
+            cache,
+            self.reading_direction,
+            self.detection_params,
+            &self.page_rotations,
+        )?);
+
+        //Preload first image
+        self.get_image(0);
+
+        Ok(())
+    }
+
+    fn open_pages(&self, path: &str) -> Result<Box<dyn PageSource>, String> {
+        let archive = ZipArchive::new(path)?;
+
+        //Only keep entries that can be decoded as pages
+        let mut entries: Vec<ZipEntryInfo> = archive
+            .entries
+            .iter()
+            .filter(|entry| is_page_file(&entry.name))
+            .cloned()
+            .collect();
+        sort_pages(&mut entries, self.page_order, |entry| {
+            (entry.name.as_str(), entry.modified)
+        });
+
+        Ok(Box::new(ZipPageSource {
+            path: path.to_string(),
+            archive,
+            entries,
+        }))
+    }
+
+    fn get_image(&mut self, index: usize) -> Option<&Image> {
+        self.document.as_mut()?.get_image(index)
+    }
+
+    fn chunk_cache(&self) -> Option<ChunkCache> {
+        Some(self.document.as_ref()?.chunk_cache())
+    }
+
+    fn set_reading_direction(&mut self, direction: ReadingDirection) {
+        self.reading_direction = direction;
+    }
+
+    fn set_detection_params(&mut self, params: DetectionParams) {
+        self.detection_params = params;
+    }
+
+    fn set_page_order(&mut self, order: PageOrder) {
+        self.page_order = order;
+    }
+
+    fn set_page_rotations(&mut self, rotations: HashMap<String, PageRotation>) {
+        self.page_rotations = rotations;
+    }
+
+    fn unload(&mut self) {
+        self.document = None;
+    }
+
+    fn can_open(&self, document_path: &str) -> bool {
+        let path = Path::new(document_path);
+
+        if !path.is_file() {
+            return false;
+        }
+
+        match extension_of(document_path) {
+            Some(extension) => ARCHIVE_EXTENSIONS.contains(&extension.as_str()),
+            None => false,
+        }
+    }
+}
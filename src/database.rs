@@ -1,39 +1,219 @@
-use std::path::Path;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
 use raylib::prelude::Rectangle;
-use rusqlite::{Connection, Error, Row};
+use rusqlite::{named_params, Connection, Error, Params, Row};
 
-use crate::structs::{Chunk, ComicMetadata};
+mod migrations;
+
+use migrations::migrate;
+
+use crate::{
+    application::get_time,
+    processing::DETECTOR_VERSION,
+    series::guess_volume,
+    structs::{
+        Bookmark, Chunk, ChunkCache, Collection, ComicMetadata, DetectionParams, DocumentRecord,
+        ImportSummary, LibraryEntry, LibraryFilter, LibraryQuery, LibrarySort, PageFit, PageOrder,
+        PageRecord, PageRotation, ReadingDirection, ReadingSession, ReadingStatistics,
+        ScannedDocument, Series, SeriesStatistics, ViewMode,
+    },
+};
+
+//Settings key holding the global detection parameters
+const DETECTION_PARAMS_KEY: &str = "detection_params";
+
+//Library documents matching a query, joined with their series
+const LIBRARY_FILTER: &str = "
+    Metadata LEFT JOIN Series ON Series.id==Metadata.series_id
+    WHERE
+        (Metadata.title LIKE :search ESCAPE '\\'
+            OR Metadata.path LIKE :search ESCAPE '\\'
+            OR Series.name LIKE :search ESCAPE '\\')
+        AND missing==0
+        AND (:opened_only==0 OR last_time_open>0)
+        AND (:series IS NULL OR Metadata.series_id==:series)
+        AND (:favourites==0 OR favourite==1)
+        AND (:tag IS NULL OR Metadata.path IN (
+            SELECT path FROM DocumentTags JOIN Tags ON Tags.id==DocumentTags.tag_id WHERE Tags.name==:tag))
+        AND (:collection IS NULL OR Metadata.path IN (
+            SELECT path FROM CollectionDocuments WHERE collection_id==:collection))";
+
+//How much of a document was read, from 0 to 1. By pages, known as soon as a document is opened,
+//or by chunks for documents not closed since page counts are stored, if they were fully segmented
+const COMPLETION: &str = "
+    CASE
+        WHEN page_count>0 THEN MIN(last_page+1, page_count)*1.0/page_count
+        WHEN chunk_count>0 THEN MIN(last_chunk+1, chunk_count)*1.0/chunk_count
+        ELSE 0
+    END";
+
+//Columns read by sqlite_row_to_metadata
+const METADATA_COLUMNS: &str = "
+    Metadata.last_time_open,
+    Metadata.path,
+    Metadata.chunk_count,
+    Metadata.page_count,
+    Metadata.last_chunk,
+    Metadata.icon,
+    Metadata.reading_direction,
+    Metadata.detection_params,
+    Metadata.page_order,
+    Metadata.title,
+    Metadata.favourite,
+    Metadata.view_mode,
+    Metadata.page_fit,
+    Metadata.spread_shift,
+    Metadata.last_page,
+    Metadata.page_offset";
+
+//Documents sharing this value are shown as a single library entry
+const LIBRARY_GROUP: &str =
+    "COALESCE(CASE WHEN :group THEN Metadata.series_id END, -Metadata.rowid)";
 
 pub struct Database {
     pub conn: Connection,
 }
 
-#[allow(dead_code, unused)]
 impl Database {
-    pub fn get_recents(&mut self) -> Vec<ComicMetadata> {
-        let mut query = self
-            .conn
-            .prepare(
-                "
-                        SELECT
-                            *
-                        FROM
-                            Metadata
-                            ORDER BY last_time_open DESC
-                            LIMIT 8;",
+    //Most recently opened documents, favourites are listed even if never opened
+    pub fn get_recents(&mut self, filter: &LibraryFilter) -> Vec<ComicMetadata> {
+        self.query_library(&LibraryQuery {
+            limit: 8,
+            opened_only: *filter == LibraryFilter::All,
+            filter: filter.clone(),
+            ..LibraryQuery::default()
+        })
+        .into_iter()
+        .map(|entry| entry.document)
+        .collect()
+    }
+
+    //Documents matching a library search, sorted and paged
+    pub fn query_library(&self, query: &LibraryQuery) -> Vec<LibraryEntry> {
+        let progress_order = format!("{COMPLETION} DESC, group_time DESC");
+        let order = match (query.series, query.sort) {
+            (Some(_), _) => "volume ASC, title COLLATE NOCASE ASC",
+            (None, LibrarySort::LastOpened) => "group_time DESC",
+            (None, LibrarySort::Title) => "COALESCE(series_name, title) COLLATE NOCASE ASC",
+            (None, LibrarySort::Progress) => progress_order.as_str(),
+        };
+
+        //Series are represented by the volume read last, or their first one
+        let mut stmt = match self.conn.prepare(&format!(
+            "
+            SELECT
+                *
+            FROM (
+                SELECT
+                    {METADATA_COLUMNS},
+                    Metadata.series_id,
+                    Metadata.volume,
+                    Series.name AS series_name,
+                    ROW_NUMBER() OVER (document_group ORDER BY last_time_open DESC, volume ASC) AS group_rank,
+                    COUNT(*) OVER document_group AS group_size,
+                    MAX(last_time_open) OVER document_group AS group_time
+                FROM {LIBRARY_FILTER}
+                WINDOW document_group AS (PARTITION BY {LIBRARY_GROUP})
             )
-            .expect("Error getting recent list from DB");
+            WHERE group_rank==1
+            ORDER BY {order}
+            LIMIT :limit OFFSET :offset;"
+        )) {
+            Ok(it) => it,
+            Err(error) => {
+                eprintln!("Error querying library: {error:?}");
+                return Vec::new();
+            }
+        };
 
-        let mut rows = query.query([]).expect("Error getting rows from DB");
+        let group = query.group_series && query.series.is_none();
+        let (favourites, tag, collection) = filter_params(&query.filter);
 
-        let mut metadata = rows
-            .mapped(sqlite_row_to_metadata)
-            .filter(|x| x.is_ok())
-            .map(|x| x.unwrap())
-            .collect::<Vec<ComicMetadata>>();
-        metadata.sort_by(|a, b| b.last_time_opened.cmp(&a.last_time_opened));
-        metadata
+        let documents = match stmt.query(named_params! {
+            ":search": like_pattern(&query.search),
+            ":opened_only": query.opened_only,
+            ":series": query.series,
+            ":favourites": favourites,
+            ":tag": tag,
+            ":collection": collection,
+            ":group": group,
+            ":limit": query.limit,
+            ":offset": query.offset,
+        }) {
+            Ok(rows) => rows
+                .mapped(|row| {
+                    let group_size: usize = row.get("group_size")?;
+                    let series = match row.get::<_, Option<i64>>("series_id")? {
+                        Some(id) if group && group_size > 1 => Some(Series {
+                            id,
+                            name: row.get("series_name")?,
+                            volume_count: group_size,
+                        }),
+                        _ => None,
+                    };
+
+                    Ok(LibraryEntry {
+                        document: sqlite_row_to_metadata(row)?,
+                        series,
+                    })
+                })
+                .filter_map(|x| x.ok())
+                .collect::<Vec<LibraryEntry>>(),
+            Err(error) => {
+                eprintln!("Error querying library: {error:?}");
+                Vec::new()
+            }
+        };
+
+        documents
+    }
+
+    //How many entries a library query returns without paging
+    pub fn count_library(&self, query: &LibraryQuery) -> usize {
+        let (favourites, tag, collection) = filter_params(&query.filter);
+
+        self.conn
+            .query_row(
+                &format!("SELECT COUNT(DISTINCT {LIBRARY_GROUP}) FROM {LIBRARY_FILTER};"),
+                named_params! {
+                    ":search": like_pattern(&query.search),
+                    ":opened_only": query.opened_only,
+                    ":series": query.series,
+                    ":favourites": favourites,
+                    ":tag": tag,
+                    ":collection": collection,
+                    ":group": query.group_series && query.series.is_none(),
+                },
+                |row| row.get(0),
+            )
+            .unwrap_or(0)
+    }
+
+    //The volume following a document in its series, if there is one
+    pub fn next_volume(&self, path: &str) -> Option<ComicMetadata> {
+        self.conn
+            .query_row(
+                &format!(
+                    "
+                SELECT
+                    {METADATA_COLUMNS}
+                FROM
+                    Metadata, Metadata AS Current
+                WHERE
+                    Current.path==?
+                    AND Metadata.series_id==Current.series_id
+                    AND Metadata.volume>Current.volume
+                    AND Metadata.missing==0
+                ORDER BY Metadata.volume ASC
+                LIMIT 1;"
+                ),
+                [path],
+                sqlite_row_to_metadata,
+            )
+            .ok()
     }
 
     pub fn save_metadata(&mut self, metadata: &Vec<&ComicMetadata>) -> Result<(), rusqlite::Error> {
@@ -44,61 +224,83 @@ impl Database {
 
         for md in metadata.iter() {
             tx.execute(
-                "INSERT OR REPLACE INTO Metadata VALUES(?,?,?,?,?)",
+                "
+                INSERT INTO
+                Metadata(
+                    last_time_open,
+                    path,
+                    chunk_count,
+                    last_chunk,
+                    icon,
+                    reading_direction,
+                    detection_params,
+                    page_order,
+                    title,
+                    view_mode,
+                    page_fit,
+                    spread_shift,
+                    last_page,
+                    page_offset,
+                    page_count
+                ) VALUES(?,?,?,?,?,?,?,?,?,?,?,?,?,?,?)
+                ON CONFLICT(path) DO UPDATE SET
+                    last_time_open=excluded.last_time_open,
+                    chunk_count=excluded.chunk_count,
+                    last_chunk=excluded.last_chunk,
+                    icon=excluded.icon,
+                    reading_direction=excluded.reading_direction,
+                    detection_params=excluded.detection_params,
+                    page_order=excluded.page_order,
+                    title=excluded.title,
+                    view_mode=excluded.view_mode,
+                    page_fit=excluded.page_fit,
+                    spread_shift=excluded.spread_shift,
+                    last_page=excluded.last_page,
+                    page_offset=excluded.page_offset,
+                    page_count=excluded.page_count,
+                    missing=0",
                 (
                     md.last_time_opened,
                     &md.path,
                     md.chunk_count,
                     md.last_seen_chunk,
                     md.thumbnail.as_ref().unwrap_or(&Vec::new()),
+                    md.reading_direction.to_i64(),
+                    md.detection_params.map(|it| it.to_config_string()),
+                    md.page_order.to_i64(),
+                    &md.title,
+                    md.view_mode.to_i64(),
+                    md.page_fit.to_i64(),
+                    md.spread_shift,
+                    md.last_seen_page,
+                    md.page_offset,
+                    md.page_count,
                 ),
             )
             .expect("Error inserting metadata into transaction");
+
+            //Other columns, like the favourite flag, are kept as they are
+            assign_series(&tx, &md.path);
         }
         tx.commit()?;
 
         Ok(())
     }
 
-    pub fn new() -> Self {
-        let conn = Connection::open("metadata.sqlite3").expect("Couldn't open metadata database");
+    pub fn new(path: &Path) -> Self {
+        let mut conn = Connection::open(path).expect("Couldn't open metadata database");
 
-        conn.execute(
-            "
-            CREATE TABLE IF NOT EXISTS
-            Metadata(
-                last_time_open INTEGER,
-                path TEXT PRIMARY KEY,
-                chunk_count INTEGER,
-                last_chunk INTEGER,
-                icon BLOB
-            )",
-            [],
-        )
-        .expect("Error creating metadata table");
-
-        conn.execute(
-            "
-            CREATE TABLE IF NOT EXISTS
-            Chunks(
-                path TEXT,
-                x INTEGER,
-                y INTEGER,
-                w INTEGER,
-                h INTEGER,
-                texture_index INTEGER,
-                CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\", \"y\") ON CONFLICT IGNORE
-            );",
-            [],
-        )
-        .expect("Error creating chunks table");
+        //Older databases are upgraded here, before anything reads them
+        if let Err(error) = migrate(&mut conn) {
+            panic!("{error}");
+        }
 
         Self { conn }
     }
 
     pub fn metadata_for(&self, path: &str) -> Option<ComicMetadata> {
         match self.conn.query_row(
-            "SELECT * FROM Metadata WHERE Path==? LIMIT 1;",
+            &format!("SELECT {METADATA_COLUMNS} FROM Metadata WHERE path==? LIMIT 1;"),
             [path],
             sqlite_row_to_metadata,
         ) {
@@ -113,12 +315,12 @@ impl Database {
         }
     }
 
-    pub fn chunks_for(&self, path: &str) -> Vec<Chunk> {
-        if let Ok(mut stmt) = self
-            .conn
-            .prepare("SELECT x,y,w,h,texture_index FROM Chunks WHERE Path==?;")
-        {
-            if let Ok(results) = stmt.query([path]) {
+    //Cached chunks for a document, only if they were computed by this detector with the same params
+    pub fn chunks_for(&self, path: &str, params: &DetectionParams) -> Vec<Chunk> {
+        if let Ok(mut stmt) = self.conn.prepare(
+            "SELECT x,y,w,h,texture_index FROM Chunks WHERE Path==? AND params_hash==? AND detector_version==?;",
+        ) {
+            if let Ok(results) = stmt.query((path, params.params_hash(), DETECTOR_VERSION)) {
                 return results
                     .mapped(sqlite_row_to_chunk)
                     .filter(|x| x.is_ok())
@@ -130,14 +332,77 @@ impl Database {
         Vec::new()
     }
 
-    pub fn save_chunk_cache(&mut self, path: String, all_chunks: Vec<Chunk>) {
-        let mut tx = self
+    //Files the cached chunks of a document were detected on, indexed by texture_index
+    pub fn pages_for(&self, path: &str) -> Vec<PageRecord> {
+        if let Ok(mut stmt) = self.conn.prepare(
+            "SELECT name,size,modified,rotation FROM Pages WHERE path==? ORDER BY texture_index;",
+        ) {
+            if let Ok(results) = stmt.query([path]) {
+                return results
+                    .mapped(|row| {
+                        Ok(PageRecord {
+                            name: row.get("name")?,
+                            size: row.get("size")?,
+                            modified: row.get("modified")?,
+                            rotation: PageRotation::from_i64(row.get("rotation")?),
+                        })
+                    })
+                    .filter_map(|x| x.ok())
+                    .collect::<Vec<PageRecord>>();
+            }
+        }
+
+        Vec::new()
+    }
+
+    pub fn clear_chunk_cache(&mut self, path: &str) {
+        if let Err(error) = self
+            .conn
+            .execute("DELETE FROM Chunks WHERE path==?;", [path])
+        {
+            eprintln!("Error clearing chunk cache for {path}: {error:?}");
+        }
+    }
+
+    //Replace the stored chunks and pages of a document
+    pub fn save_chunk_cache(&mut self, path: String, cache: &ChunkCache, params: &DetectionParams) {
+        let tx = self
             .conn
             .transaction()
             .expect("Couldn't start transaction to save chunks!");
 
-        if let Ok(mut stmt) = tx.prepare("INSERT INTO Chunks VALUES(?,?,?,?,?,?);") {
-            for c in all_chunks {
+        let params_hash = params.params_hash();
+
+        //Stale chunks may point at pages that moved or changed
+        for table in ["Chunks", "Pages"] {
+            if let Err(error) = tx.execute(&format!("DELETE FROM {table} WHERE path==?;"), [&path])
+            {
+                eprintln!("Error removing stale {table} rows for {path}: {error:?}");
+            }
+        }
+
+        if let Ok(mut stmt) = tx.prepare(
+            "INSERT INTO Pages(path, texture_index, name, size, modified, rotation) VALUES(?,?,?,?,?,?);",
+        ) {
+            for (index, page) in cache.pages.iter().enumerate() {
+                stmt.execute((
+                    &path,
+                    index,
+                    &page.name,
+                    page.size,
+                    page.modified,
+                    page.rotation.to_i64(),
+                ))
+                .expect("Error inserting Page row into db");
+            }
+        } else {
+            println!("Couldn't prepare statement to insert pages into DB");
+        }
+
+        if let Ok(mut stmt) = tx.prepare(
+            "INSERT INTO Chunks(path,x,y,w,h,texture_index,params_hash,detector_version) VALUES(?,?,?,?,?,?,?,?);",
+        ) {
+            for c in cache.chunks.iter() {
                 stmt.execute((
                     &path,
                     c.rect.x,
@@ -145,6 +410,8 @@ impl Database {
                     c.rect.width,
                     c.rect.height,
                     c.texture_index,
+                    params_hash,
+                    DETECTOR_VERSION,
                 ))
                 .expect("Error inserting Chunk row into db");
             }
@@ -152,33 +419,1122 @@ impl Database {
             println!("Couldn't prepare statement to insert chunks into DB");
         }
 
-        tx.commit();
+        if let Err(error) = tx.commit() {
+            eprintln!("Error saving chunks for {path}: {error:?}");
+        }
+    }
+
+    //Turns the reader applied to pages of a document, by page name
+    pub fn page_rotations_for(&self, path: &str) -> HashMap<String, PageRotation> {
+        if let Ok(mut stmt) = self
+            .conn
+            .prepare("SELECT name,rotation FROM PageRotations WHERE path==?;")
+        {
+            if let Ok(results) = stmt.query([path]) {
+                return results
+                    .mapped(|row| {
+                        Ok((
+                            row.get::<_, String>("name")?,
+                            PageRotation::from_i64(row.get("rotation")?),
+                        ))
+                    })
+                    .filter_map(|x| x.ok())
+                    .collect();
+            }
+        }
+
+        HashMap::new()
+    }
+
+    pub fn set_page_rotation(&mut self, path: &str, name: &str, rotation: PageRotation) {
+        let result = if rotation == PageRotation::None {
+            self.conn.execute(
+                "DELETE FROM PageRotations WHERE path==? AND name==?;",
+                (path, name),
+            )
+        } else {
+            self.conn.execute(
+                "INSERT INTO PageRotations(path, name, rotation) VALUES(?,?,?);",
+                (path, name, rotation.to_i64()),
+            )
+        };
+
+        if let Err(error) = result {
+            eprintln!("Error saving rotation of {name} in {path}: {error:?}");
+        }
+    }
+
+    //Bookmarks of a document, in reading order
+    pub fn bookmarks_for(&self, path: &str) -> Vec<Bookmark> {
+        if let Ok(mut stmt) = self.conn.prepare(
+            "SELECT texture_index,x,y,w,h,note,created FROM Bookmarks WHERE path==? ORDER BY texture_index, y, x;",
+        ) {
+            if let Ok(results) = stmt.query([path]) {
+                return results
+                    .mapped(|row| {
+                        Ok(Bookmark {
+                            texture_index: row.get("texture_index")?,
+                            rect: Rectangle::new(
+                                row.get("x")?,
+                                row.get("y")?,
+                                row.get("w")?,
+                                row.get("h")?,
+                            ),
+                            note: row
+                                .get::<_, Option<String>>("note")?
+                                .unwrap_or_default(),
+                            created: row.get("created")?,
+                        })
+                    })
+                    .filter_map(|x| x.ok())
+                    .collect::<Vec<Bookmark>>();
+            }
+        }
+
+        Vec::new()
+    }
+
+    //Add a bookmark, or update the note of the one already on the same chunk
+    pub fn save_bookmark(&mut self, path: &str, bookmark: &Bookmark) {
+        if let Err(error) = self.conn.execute(
+            "INSERT INTO Bookmarks(path, texture_index, x, y, w, h, note, created) VALUES(?,?,?,?,?,?,?,?);",
+            (
+                path,
+                bookmark.texture_index,
+                bookmark.rect.x,
+                bookmark.rect.y,
+                bookmark.rect.width,
+                bookmark.rect.height,
+                &bookmark.note,
+                bookmark.created,
+            ),
+        ) {
+            eprintln!("Error saving bookmark for {path}: {error:?}");
+        }
+    }
+
+    pub fn remove_bookmark(&mut self, path: &str, bookmark: &Bookmark) {
+        if let Err(error) = self.conn.execute(
+            "DELETE FROM Bookmarks WHERE path==? AND texture_index==? AND x==? AND y==?;",
+            (
+                path,
+                bookmark.texture_index,
+                bookmark.rect.x,
+                bookmark.rect.y,
+            ),
+        ) {
+            eprintln!("Error removing bookmark for {path}: {error:?}");
+        }
+    }
+
+    pub fn log_session(&mut self, session: &ReadingSession) {
+        if let Err(error) = self.conn.execute(
+            "INSERT INTO ReadingSessions(path, started, ended, chunks_advanced, pages_seen) VALUES(?,?,?,?,?);",
+            (
+                &session.path,
+                session.started,
+                session.ended,
+                session.chunks_advanced,
+                session.pages_seen,
+            ),
+        ) {
+            eprintln!("Error logging reading session: {error:?}");
+        }
+    }
+
+    //Reading log totals, time read over the last days and per series
+    pub fn reading_statistics(&self, days: usize) -> ReadingStatistics {
+        let mut statistics = ReadingStatistics::default();
+
+        if let Ok((sessions, seconds_read)) = self.conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(ended-started), 0) FROM ReadingSessions;",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ) {
+            statistics.sessions = sessions;
+            statistics.seconds_read = seconds_read;
+        }
+
+        statistics.completion = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT COALESCE(AVG({COMPLETION}), 0) FROM Metadata WHERE last_time_open>0;"
+                ),
+                [],
+                |row| row.get::<_, f64>(0),
+            )
+            .unwrap_or_default() as f32;
+
+        //Every day is listed, also those without sessions
+        if let Ok(mut stmt) = self.conn.prepare(
+            "
+            WITH RECURSIVE Days(day) AS (
+                SELECT date('now', 'localtime', ?)
+                UNION ALL
+                SELECT date(day, '+1 day') FROM Days WHERE day<date('now', 'localtime')
+            )
+            SELECT
+                Days.day AS day,
+                COALESCE(SUM(ended-started), 0) AS seconds
+            FROM
+                Days LEFT JOIN ReadingSessions
+                ON date(started, 'unixepoch', 'localtime')==Days.day
+            GROUP BY Days.day
+            ORDER BY Days.day;",
+        ) {
+            if let Ok(results) = stmt.query([format!("-{} days", days.saturating_sub(1))]) {
+                statistics.days = results
+                    .mapped(|row| Ok((row.get("day")?, row.get("seconds")?)))
+                    .filter_map(|x| x.ok())
+                    .collect();
+            }
+        }
+
+        //Series are listed once any of their volumes was opened
+        if let Ok(mut stmt) = self.conn.prepare(&format!(
+            "
+            SELECT
+                COALESCE(Series.name, Metadata.title, Metadata.path) AS name,
+                COALESCE(SUM(Time.seconds), 0) AS seconds,
+                AVG({COMPLETION}) AS completion
+            FROM
+                Metadata
+                LEFT JOIN Series ON Series.id==Metadata.series_id
+                LEFT JOIN (
+                    SELECT path, SUM(ended-started) AS seconds FROM ReadingSessions GROUP BY path
+                ) AS Time ON Time.path==Metadata.path
+            WHERE missing==0
+            GROUP BY COALESCE(Metadata.series_id, -Metadata.rowid)
+            HAVING MAX(last_time_open)>0
+            ORDER BY seconds DESC, name COLLATE NOCASE;"
+        )) {
+            if let Ok(results) = stmt.query([]) {
+                statistics.series = results
+                    .mapped(|row| {
+                        Ok(SeriesStatistics {
+                            name: row.get("name")?,
+                            seconds_read: row.get("seconds")?,
+                            completion: row.get::<_, f64>("completion")? as f32,
+                        })
+                    })
+                    .filter_map(|x| x.ok())
+                    .collect();
+            }
+        }
+
+        statistics
+    }
+
+    pub fn set_favourite(&mut self, path: &str, favourite: bool) {
+        if let Err(error) = self.conn.execute(
+            "UPDATE Metadata SET favourite=? WHERE path==?;",
+            (favourite, path),
+        ) {
+            eprintln!("Error saving favourite for {path}: {error:?}");
+        }
+    }
+
+    //Every tag in use, alphabetically
+    pub fn tags(&self) -> Vec<String> {
+        self.string_column("SELECT name FROM Tags ORDER BY name COLLATE NOCASE;", [])
+    }
+
+    pub fn tags_for(&self, path: &str) -> Vec<String> {
+        self.string_column(
+            "
+            SELECT
+                name
+            FROM
+                Tags JOIN DocumentTags ON DocumentTags.tag_id==Tags.id
+            WHERE path==?
+            ORDER BY name COLLATE NOCASE;",
+            [path],
+        )
+    }
+
+    //Tag a document, creating the tag if it doesn't exist
+    pub fn add_tag(&mut self, path: &str, tag: &str) {
+        let result = self
+            .conn
+            .execute("INSERT OR IGNORE INTO Tags(name) VALUES(?);", [tag])
+            .and_then(|_| {
+                self.conn.execute(
+                    "INSERT INTO DocumentTags(path, tag_id) SELECT ?, id FROM Tags WHERE name==?;",
+                    [path, tag],
+                )
+            });
+
+        if let Err(error) = result {
+            eprintln!("Error tagging {path} with {tag}: {error:?}");
+        }
+    }
+
+    //Untag a document, tags left without documents are deleted
+    pub fn remove_tag(&mut self, path: &str, tag: &str) {
+        let result = self
+            .conn
+            .execute(
+                "DELETE FROM DocumentTags WHERE path==? AND tag_id IN (SELECT id FROM Tags WHERE name==?);",
+                [path, tag],
+            )
+            .and_then(|_| {
+                self.conn.execute(
+                    "DELETE FROM Tags WHERE id NOT IN (SELECT tag_id FROM DocumentTags);",
+                    [],
+                )
+            });
+
+        if let Err(error) = result {
+            eprintln!("Error removing tag {tag} from {path}: {error:?}");
+        }
+    }
+
+    //Rename a tag on every document, merging it into the tag already named like that
+    pub fn rename_tag(&mut self, tag: &str, new_name: &str) {
+        let renamed = match self.conn.execute(
+            "UPDATE OR IGNORE Tags SET name=?2 WHERE name==?1;",
+            [tag, new_name],
+        ) {
+            Ok(it) => it,
+            Err(error) => {
+                eprintln!("Error renaming tag {tag}: {error:?}");
+                return;
+            }
+        };
+
+        if renamed > 0 {
+            return;
+        }
+
+        if let Err(error) = self.conn.execute(
+            "
+            UPDATE OR IGNORE DocumentTags SET tag_id=(SELECT id FROM Tags WHERE name==?2)
+            WHERE tag_id IN (SELECT id FROM Tags WHERE name==?1);",
+            [tag, new_name],
+        ) {
+            eprintln!("Error merging tag {tag} into {new_name}: {error:?}");
+        }
+
+        //Documents that had both tags are left with the old one
+        self.delete_tag(tag);
+    }
+
+    pub fn delete_tag(&mut self, tag: &str) {
+        let result = self
+            .conn
+            .execute(
+                "DELETE FROM DocumentTags WHERE tag_id IN (SELECT id FROM Tags WHERE name==?);",
+                [tag],
+            )
+            .and_then(|_| self.conn.execute("DELETE FROM Tags WHERE name==?;", [tag]));
+
+        if let Err(error) = result {
+            eprintln!("Error deleting tag {tag}: {error:?}");
+        }
+    }
+
+    //Every collection, alphabetically
+    pub fn collections(&self) -> Vec<Collection> {
+        self.collection_rows(
+            "SELECT id, name FROM Collections ORDER BY name COLLATE NOCASE;",
+            [],
+        )
+    }
+
+    //Collections holding a document
+    pub fn collections_for(&self, path: &str) -> Vec<Collection> {
+        self.collection_rows(
+            "
+            SELECT
+                id, name
+            FROM
+                Collections JOIN CollectionDocuments ON CollectionDocuments.collection_id==Collections.id
+            WHERE path==?
+            ORDER BY name COLLATE NOCASE;",
+            [path],
+        )
+    }
+
+    //Create a collection, or find the one with the same name
+    pub fn create_collection(&mut self, name: &str) -> Option<i64> {
+        let result = self
+            .conn
+            .execute("INSERT OR IGNORE INTO Collections(name) VALUES(?);", [name])
+            .and_then(|_| {
+                self.conn
+                    .query_row("SELECT id FROM Collections WHERE name==?;", [name], |row| {
+                        row.get(0)
+                    })
+            });
+
+        match result {
+            Ok(id) => Some(id),
+            Err(error) => {
+                eprintln!("Error creating collection {name}: {error:?}");
+                None
+            }
+        }
+    }
+
+    pub fn rename_collection(&mut self, id: i64, name: &str) {
+        if let Err(error) = self
+            .conn
+            .execute("UPDATE Collections SET name=? WHERE id==?;", (name, id))
+        {
+            eprintln!("Error renaming collection {id}: {error:?}");
+        }
+    }
+
+    pub fn delete_collection(&mut self, id: i64) {
+        let result = self
+            .conn
+            .execute(
+                "DELETE FROM CollectionDocuments WHERE collection_id==?;",
+                [id],
+            )
+            .and_then(|_| {
+                self.conn
+                    .execute("DELETE FROM Collections WHERE id==?;", [id])
+            });
+
+        if let Err(error) = result {
+            eprintln!("Error deleting collection {id}: {error:?}");
+        }
+    }
+
+    pub fn add_to_collection(&mut self, id: i64, path: &str) {
+        if let Err(error) = self.conn.execute(
+            "INSERT INTO CollectionDocuments(collection_id, path, added) VALUES(?,?,?);",
+            (id, path, get_time()),
+        ) {
+            eprintln!("Error adding {path} to collection {id}: {error:?}");
+        }
+    }
+
+    pub fn remove_from_collection(&mut self, id: i64, path: &str) {
+        if let Err(error) = self.conn.execute(
+            "DELETE FROM CollectionDocuments WHERE collection_id==? AND path==?;",
+            (id, path),
+        ) {
+            eprintln!("Error removing {path} from collection {id}: {error:?}");
+        }
+    }
+
+    fn string_column(&self, sql: &str, params: impl Params) -> Vec<String> {
+        if let Ok(mut stmt) = self.conn.prepare(sql) {
+            if let Ok(results) = stmt.query(params) {
+                return results
+                    .mapped(|row| row.get(0))
+                    .filter_map(|x| x.ok())
+                    .collect::<Vec<String>>();
+            }
+        }
+
+        Vec::new()
+    }
+
+    fn collection_rows(&self, sql: &str, params: impl Params) -> Vec<Collection> {
+        if let Ok(mut stmt) = self.conn.prepare(sql) {
+            if let Ok(results) = stmt.query(params) {
+                return results
+                    .mapped(|row| {
+                        Ok(Collection {
+                            id: row.get("id")?,
+                            name: row.get("name")?,
+                        })
+                    })
+                    .filter_map(|x| x.ok())
+                    .collect::<Vec<Collection>>();
+            }
+        }
+
+        Vec::new()
+    }
+
+    //Folders scanned for documents
+    pub fn library_roots(&self) -> Vec<String> {
+        if let Ok(mut stmt) = self
+            .conn
+            .prepare("SELECT path FROM LibraryRoots ORDER BY path;")
+        {
+            if let Ok(results) = stmt.query([]) {
+                return results
+                    .mapped(|row| row.get(0))
+                    .filter_map(|x| x.ok())
+                    .collect::<Vec<String>>();
+            }
+        }
+
+        Vec::new()
+    }
+
+    pub fn add_library_root(&mut self, path: &str) {
+        if let Err(error) = self.conn.execute(
+            "INSERT OR IGNORE INTO LibraryRoots(path) VALUES(?);",
+            [path],
+        ) {
+            eprintln!("Error adding library root {path}: {error:?}");
+        }
+    }
+
+    pub fn remove_library_root(&mut self, path: &str) {
+        if let Err(error) = self
+            .conn
+            .execute("DELETE FROM LibraryRoots WHERE path==?;", [path])
+        {
+            eprintln!("Error removing library root {path}: {error:?}");
+        }
+    }
+
+    pub fn set_fingerprint(&mut self, path: &str, fingerprint: &str) {
+        if let Err(error) = self.conn.execute(
+            "UPDATE Metadata SET fingerprint=? WHERE path==?;",
+            (fingerprint, path),
+        ) {
+            eprintln!("Error saving fingerprint for {path}: {error:?}");
+        }
+    }
+
+    pub fn set_thumbnail(&mut self, path: &str, thumbnail: &[u8]) {
+        if let Err(error) = self.conn.execute(
+            "UPDATE Metadata SET icon=? WHERE path==?;",
+            (thumbnail, path),
+        ) {
+            eprintln!("Error saving thumbnail for {path}: {error:?}");
+        }
+    }
+
+    //Give a document without progress of its own the progress, chunks and bookmarks of the same
+    //document stored under a path that no longer exists, returns true if it was moved
+    pub fn relink_document(&mut self, path: &str, fingerprint: &str) -> bool {
+        //Opening the moved copy once, before relinking existed or while the old path was still
+        //around, shouldn't orphan the history for good
+        let has_progress = self
+            .conn
+            .query_row(
+                "SELECT (IFNULL(last_chunk, 0)>0 OR IFNULL(last_page, 0)>0
+                    OR EXISTS(SELECT 1 FROM Bookmarks WHERE Bookmarks.path==Metadata.path)) AS progress
+                FROM Metadata WHERE path==?;",
+                [path],
+                |row| row.get::<_, bool>("progress"),
+            )
+            .unwrap_or(false);
+
+        if has_progress {
+            return false;
+        }
+
+        let candidates = self.string_column(
+            "SELECT path FROM Metadata WHERE fingerprint==? AND path!=? ORDER BY last_time_open DESC;",
+            [fingerprint, path],
+        );
+
+        //Copies of a document in both places are kept apart
+        let old_path = match candidates.into_iter().find(|it| !Path::new(it).exists()) {
+            Some(it) => it,
+            None => return false,
+        };
+
+        let tx = match self.conn.transaction() {
+            Ok(it) => it,
+            Err(error) => {
+                eprintln!("Error starting relink: {error:?}");
+                return false;
+            }
+        };
+
+        //Rows made for the new path by a library scan are replaced
+        for table in DOCUMENT_TABLES {
+            if let Err(error) = tx.execute(&format!("DELETE FROM {table} WHERE path==?;"), [path]) {
+                eprintln!("Error clearing {path} in {table}: {error:?}");
+            }
+        }
+        move_document(&tx, &old_path, path);
+
+        match tx.commit() {
+            Ok(_) => {
+                log::info!("Relinked {old_path} to {path}");
+                true
+            }
+            Err(error) => {
+                eprintln!("Error relinking {old_path} to {path}: {error:?}");
+                false
+            }
+        }
+    }
+
+    //Progress, bookmarks, tags and collections of every document, to be carried to another machine
+    pub fn export_records(&self) -> Vec<DocumentRecord> {
+        let roots = self.library_roots();
+
+        let documents: Vec<ComicMetadata> = match self.conn.prepare(&format!(
+            "SELECT {METADATA_COLUMNS} FROM Metadata ORDER BY path;"
+        )) {
+            Ok(mut stmt) => match stmt.query([]) {
+                Ok(rows) => rows
+                    .mapped(sqlite_row_to_metadata)
+                    .filter_map(|x| x.ok())
+                    .collect(),
+                Err(error) => {
+                    eprintln!("Error exporting documents: {error:?}");
+                    return Vec::new();
+                }
+            },
+            Err(error) => {
+                eprintln!("Error exporting documents: {error:?}");
+                return Vec::new();
+            }
+        };
+
+        documents
+            .into_iter()
+            .map(|metadata| DocumentRecord {
+                library_path: library_path(&metadata.path, &roots),
+                fingerprint: self
+                    .conn
+                    .query_row(
+                        "SELECT fingerprint FROM Metadata WHERE path==?;",
+                        [&metadata.path],
+                        |row| row.get("fingerprint"),
+                    )
+                    .unwrap_or_default(),
+                tags: self.tags_for(&metadata.path),
+                collections: self
+                    .collections_for(&metadata.path)
+                    .into_iter()
+                    .map(|it| it.name)
+                    .collect(),
+                bookmarks: self.bookmarks_for(&metadata.path),
+                //Thumbnails are made again from the local files
+                metadata: ComicMetadata {
+                    thumbnail: None,
+                    ..metadata
+                },
+            })
+            .collect()
+    }
+
+    //Merge records exported on another machine, the most recently opened progress wins.
+    //Tags, collections and bookmarks are only ever added
+    pub fn import_records(&mut self, records: &[DocumentRecord]) -> ImportSummary {
+        let roots = self.library_roots();
+        let local: HashMap<String, u64> = match self
+            .conn
+            .prepare("SELECT path, last_time_open FROM Metadata;")
+        {
+            Ok(mut stmt) => match stmt.query([]) {
+                Ok(rows) => rows
+                    .mapped(|row| Ok((row.get("path")?, row.get("last_time_open")?)))
+                    .filter_map(|x| x.ok())
+                    .collect(),
+                Err(_) => HashMap::new(),
+            },
+            Err(_) => HashMap::new(),
+        };
+
+        //Documents that can be told apart by file name or fingerprint alone
+        let mut by_name: HashMap<String, Vec<&String>> = HashMap::new();
+        for path in local.keys() {
+            by_name.entry(title_from_path(path)).or_default().push(path);
+        }
+
+        let fingerprints: Vec<(String, String)> = match self
+            .conn
+            .prepare("SELECT path, fingerprint FROM Metadata WHERE fingerprint IS NOT NULL;")
+        {
+            Ok(mut stmt) => match stmt.query([]) {
+                Ok(rows) => rows
+                    .mapped(|row| Ok((row.get("fingerprint")?, row.get("path")?)))
+                    .filter_map(|x| x.ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+        let mut by_fingerprint: HashMap<&String, Vec<&String>> = HashMap::new();
+        for (fingerprint, path) in fingerprints.iter() {
+            by_fingerprint.entry(fingerprint).or_default().push(path);
+        }
+
+        let mut summary = ImportSummary::default();
+        let tx = match self.conn.transaction() {
+            Ok(it) => it,
+            Err(error) => {
+                eprintln!("Error starting import: {error:?}");
+                return summary;
+            }
+        };
+
+        for record in records {
+            let imported = &record.metadata;
+            let path = match matching_path(record, &local, &roots, &by_fingerprint, &by_name) {
+                Some(it) => it,
+                None => {
+                    summary.unmatched += 1;
+                    continue;
+                }
+            };
+
+            let newer = imported.last_time_opened > local[&path];
+            let result = if newer {
+                summary.updated += 1;
+                tx.execute(
+                    "
+                    UPDATE Metadata SET
+                        last_time_open=?,
+                        chunk_count=?,
+                        last_chunk=?,
+                        reading_direction=?,
+                        detection_params=?,
+                        page_order=?,
+                        favourite=?,
+                        view_mode=?,
+                        page_fit=?,
+                        spread_shift=?,
+                        last_page=?,
+                        page_offset=?,
+                        page_count=?
+                    WHERE path==?;",
+                    (
+                        imported.last_time_opened,
+                        imported.chunk_count,
+                        imported.last_seen_chunk,
+                        imported.reading_direction.to_i64(),
+                        imported.detection_params.map(|it| it.to_config_string()),
+                        imported.page_order.to_i64(),
+                        imported.favourite,
+                        imported.view_mode.to_i64(),
+                        imported.page_fit.to_i64(),
+                        imported.spread_shift,
+                        imported.last_seen_page,
+                        imported.page_offset,
+                        imported.page_count,
+                        &path,
+                    ),
+                )
+                .map(|_| ())
+            } else {
+                summary.merged += 1;
+                Ok(())
+            };
+
+            let result = result.and_then(|_| {
+                for tag in record.tags.iter() {
+                    tx.execute("INSERT OR IGNORE INTO Tags(name) VALUES(?);", [tag])?;
+                    tx.execute(
+                        "INSERT INTO DocumentTags(path, tag_id) SELECT ?, id FROM Tags WHERE name==?;",
+                        [&path, tag],
+                    )?;
+                }
+
+                for collection in record.collections.iter() {
+                    tx.execute(
+                        "INSERT OR IGNORE INTO Collections(name) VALUES(?);",
+                        [collection],
+                    )?;
+                    tx.execute(
+                        "
+                        INSERT INTO CollectionDocuments(collection_id, path, added)
+                        SELECT id, ?, ? FROM Collections WHERE name==?;",
+                        (&path, get_time(), collection),
+                    )?;
+                }
+
+                //Notes of the more recent side win
+                let conflict = if newer { "REPLACE" } else { "IGNORE" };
+                for bookmark in record.bookmarks.iter() {
+                    tx.execute(
+                        &format!(
+                            "INSERT OR {conflict} INTO Bookmarks(path, texture_index, x, y, w, h, note, created) VALUES(?,?,?,?,?,?,?,?);"
+                        ),
+                        (
+                            &path,
+                            bookmark.texture_index,
+                            bookmark.rect.x,
+                            bookmark.rect.y,
+                            bookmark.rect.width,
+                            bookmark.rect.height,
+                            &bookmark.note,
+                            bookmark.created,
+                        ),
+                    )?;
+                }
+
+                Ok(())
+            });
+
+            if let Err(error) = result {
+                eprintln!("Error importing {path}: {error:?}");
+            }
+        }
+
+        if let Err(error) = tx.commit() {
+            eprintln!("Error saving import: {error:?}");
+            return ImportSummary::default();
+        }
+
+        summary
+    }
+
+    //Paths of the documents that already have a thumbnail
+    pub fn paths_with_thumbnail(&self) -> HashSet<String> {
+        if let Ok(mut stmt) = self
+            .conn
+            .prepare("SELECT path FROM Metadata WHERE length(icon)>0;")
+        {
+            if let Ok(results) = stmt.query([]) {
+                return results
+                    .mapped(|row| row.get(0))
+                    .filter_map(|x| x.ok())
+                    .collect::<HashSet<String>>();
+            }
+        }
+
+        HashSet::new()
+    }
+
+    //Bring the documents under the scanned roots in line with what the scanner found
+    pub fn sync_library(&mut self, roots: &[String], found: Vec<ScannedDocument>) {
+        let known: Vec<String> = match self.conn.prepare("SELECT path FROM Metadata;") {
+            Ok(mut stmt) => match stmt.query([]) {
+                Ok(rows) => rows
+                    .mapped(|row| row.get(0))
+                    .filter_map(|x| x.ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+
+        let found_paths: HashSet<&String> = found.iter().map(|it| &it.path).collect();
+        let known_paths: HashSet<&String> = known.iter().collect();
+
+        let fingerprints: HashMap<String, String> = self
+            .conn
+            .prepare("SELECT path, fingerprint FROM Metadata WHERE fingerprint IS NOT NULL;")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| Ok((row.get("path")?, row.get("fingerprint")?)))?
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        //Known documents under a scanned root that weren't found again
+        let mut missing: Vec<String> = known
+            .iter()
+            .filter(|path| roots.iter().any(|root| Path::new(path).starts_with(root)))
+            .filter(|path| !found_paths.contains(path))
+            .cloned()
+            .collect();
+
+        let tx = match self.conn.transaction() {
+            Ok(it) => it,
+            Err(error) => {
+                eprintln!("Error starting library sync: {error:?}");
+                return;
+            }
+        };
+
+        for document in found.iter() {
+            if !known_paths.contains(&document.path) {
+                //A single missing document with the same pages was moved here, names alone
+                //are shared by volumes of different series
+                let moved_from: Vec<usize> = missing
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, path)| {
+                        document.fingerprint.is_some()
+                            && fingerprints.get(*path) == document.fingerprint.as_ref()
+                    })
+                    .map(|(index, _)| index)
+                    .collect();
+
+                if moved_from.len() == 1 {
+                    let old_path = missing.remove(moved_from[0]);
+                    move_document(&tx, &old_path, &document.path);
+                } else if let Err(error) = tx.execute(
+                    "
+                    INSERT OR IGNORE INTO
+                    Metadata(last_time_open, path, chunk_count, last_chunk, title, fingerprint)
+                    VALUES(0, ?, 0, 0, ?, ?)",
+                    (&document.path, &document.title, &document.fingerprint),
+                ) {
+                    eprintln!("Error importing {}: {error:?}", document.path);
+                }
+
+                assign_series(&tx, &document.path);
+            }
+
+            if let Err(error) = tx.execute(
+                "UPDATE Metadata SET missing=0 WHERE path==?;",
+                [&document.path],
+            ) {
+                eprintln!("Error updating {}: {error:?}", document.path);
+            }
+
+            if let Some(thumbnail) = &document.thumbnail {
+                if let Err(error) = tx.execute(
+                    "UPDATE Metadata SET icon=? WHERE path==? AND (icon IS NULL OR length(icon)==0);",
+                    (thumbnail, &document.path),
+                ) {
+                    eprintln!("Error saving thumbnail for {}: {error:?}", document.path);
+                }
+            }
+        }
+
+        for path in missing {
+            if let Err(error) = tx.execute("UPDATE Metadata SET missing=1 WHERE path==?;", [&path])
+            {
+                eprintln!("Error updating {path}: {error:?}");
+            }
+        }
+
+        if let Err(error) = tx.commit() {
+            eprintln!("Error saving library sync: {error:?}");
+        }
+    }
+
+    //Detection parameters used by documents without their own
+    pub fn global_detection_params(&self) -> DetectionParams {
+        match self.conn.query_row(
+            "SELECT value FROM Settings WHERE key==?;",
+            [DETECTION_PARAMS_KEY],
+            |row| row.get::<_, String>(0),
+        ) {
+            Ok(config) => DetectionParams::from_config_string(&config),
+            Err(_) => DetectionParams::default(),
+        }
+    }
+
+    pub fn set_global_detection_params(&mut self, params: &DetectionParams) {
+        if let Err(error) = self.conn.execute(
+            "INSERT OR REPLACE INTO Settings(key, value) VALUES(?,?);",
+            (DETECTION_PARAMS_KEY, params.to_config_string()),
+        ) {
+            eprintln!("Error saving detection params: {error:?}");
+        }
+    }
+
+    //Override the detection parameters of a single document, None goes back to the global ones
+    pub fn set_detection_params(&mut self, path: &str, params: Option<&DetectionParams>) {
+        if let Err(error) = self.conn.execute(
+            "UPDATE Metadata SET detection_params=? WHERE path==?;",
+            (params.map(|it| it.to_config_string()), path),
+        ) {
+            eprintln!("Error saving detection params for {path}: {error:?}");
+        }
+    }
+}
+
+//Titles weren't stored by older versions, derive them from the path
+fn backfill_titles(conn: &Connection) {
+    let paths: Vec<String> = match conn.prepare("SELECT path FROM Metadata WHERE title IS NULL;") {
+        Ok(mut stmt) => match stmt.query([]) {
+            Ok(rows) => rows
+                .mapped(|row| row.get(0))
+                .filter_map(|x| x.ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        },
+        Err(_) => Vec::new(),
+    };
+
+    for path in paths {
+        if let Err(error) = conn.execute(
+            "UPDATE Metadata SET title=? WHERE path==?;",
+            (title_from_path(&path), &path),
+        ) {
+            eprintln!("Error setting title for {path}: {error:?}");
+        }
+    }
+}
+
+//Series are guessed for documents stored before they existed
+fn backfill_series(conn: &Connection) {
+    let paths: Vec<String> =
+        match conn.prepare("SELECT path FROM Metadata WHERE series_id IS NULL;") {
+            Ok(mut stmt) => match stmt.query([]) {
+                Ok(rows) => rows
+                    .mapped(|row| row.get(0))
+                    .filter_map(|x| x.ok())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            Err(_) => Vec::new(),
+        };
+
+    for path in paths {
+        assign_series(conn, &path);
+    }
+}
+
+//Store the series and volume guessed from a document's path
+fn assign_series(conn: &Connection, path: &str) {
+    let guess = match guess_volume(path) {
+        Some(it) => it,
+        None => return,
+    };
+
+    let series_id: Result<i64, Error> = conn
+        .execute(
+            "INSERT OR IGNORE INTO Series(key, name) VALUES(?,?);",
+            (&guess.key, &guess.series),
+        )
+        .and_then(|_| {
+            conn.query_row("SELECT id FROM Series WHERE key==?;", [&guess.key], |row| {
+                row.get(0)
+            })
+        });
+
+    let result = series_id.and_then(|series_id| {
+        conn.execute(
+            "UPDATE Metadata SET series_id=?, volume=? WHERE path==?;",
+            (series_id, guess.volume, path),
+        )
+    });
+
+    if let Err(error) = result {
+        eprintln!("Error assigning series for {path}: {error:?}");
+    }
+}
+
+//Document title shown in the UI, its file or folder name
+pub fn title_from_path(path: &str) -> String {
+    match Path::new(path).file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => path.to_string(),
+    }
+}
+
+//Every table keyed by document path
+const DOCUMENT_TABLES: [&str; 8] = [
+    "Metadata",
+    "Chunks",
+    "Pages",
+    "PageRotations",
+    "Bookmarks",
+    "ReadingSessions",
+    "DocumentTags",
+    "CollectionDocuments",
+];
+
+//Point everything stored about a document to its new path
+fn move_document(conn: &Connection, old_path: &str, new_path: &str) {
+    for table in DOCUMENT_TABLES {
+        if let Err(error) = conn.execute(
+            &format!("UPDATE {table} SET path=? WHERE path==?;"),
+            (new_path, old_path),
+        ) {
+            eprintln!("Error moving {old_path} in {table}: {error:?}");
+        }
     }
 }
 
+//Path of a document inside the first library folder holding it, separated by '/'
+fn library_path(path: &str, roots: &[String]) -> Option<String> {
+    roots.iter().find_map(|root| {
+        let relative = Path::new(path).strip_prefix(root).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|it| it.as_os_str().to_string_lossy().to_string())
+            .collect();
+
+        Some(parts.join("/")).filter(|it| !it.is_empty())
+    })
+}
+
+//Local document an exported record belongs to: the same path, the same place in one of
+//the library folders, or the only document with the same fingerprint or file name
+fn matching_path(
+    record: &DocumentRecord,
+    local: &HashMap<String, u64>,
+    roots: &[String],
+    by_fingerprint: &HashMap<&String, Vec<&String>>,
+    by_name: &HashMap<String, Vec<&String>>,
+) -> Option<String> {
+    let path = &record.metadata.path;
+    if local.contains_key(path) {
+        return Some(path.clone());
+    }
+
+    if let Some(library_path) = &record.library_path {
+        let found = roots.iter().find_map(|root| {
+            let candidate = library_path
+                .split('/')
+                .fold(PathBuf::from(root), |it, part| it.join(part))
+                .to_string_lossy()
+                .to_string();
+
+            local.contains_key(&candidate).then_some(candidate)
+        });
+
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    let same_document = record
+        .fingerprint
+        .as_ref()
+        .and_then(|it| by_fingerprint.get(it))
+        .map(Vec::as_slice);
+    if let Some([only]) = same_document {
+        return Some(only.to_string());
+    }
+
+    //Exported paths may use the other platform's separators
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    match by_name.get(name).map(Vec::as_slice) {
+        Some([only]) => Some(only.to_string()),
+        _ => None,
+    }
+}
+
+//Values of the :favourites, :tag and :collection parameters of LIBRARY_FILTER
+fn filter_params(filter: &LibraryFilter) -> (bool, Option<&str>, Option<i64>) {
+    match filter {
+        LibraryFilter::All => (false, None, None),
+        LibraryFilter::Favourites => (true, None, None),
+        LibraryFilter::Tag(tag) => (false, Some(tag.as_str()), None),
+        LibraryFilter::Collection(id) => (false, None, Some(*id)),
+    }
+}
+
+//LIKE pattern matching the given text anywhere, with wildcards escaped
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{escaped}%")
+}
+
 fn sqlite_row_to_chunk(row: &Row) -> Result<Chunk, Error> {
-    let texture_index: usize = row.get(4).unwrap();
+    let texture_index: usize = row.get("texture_index")?;
 
     Ok(Chunk {
-        rect: Rectangle::new(
-            row.get(0).unwrap(),
-            row.get(1).unwrap(),
-            row.get(2).unwrap(),
-            row.get(3).unwrap(),
-        ),
+        rect: Rectangle::new(row.get("x")?, row.get("y")?, row.get("w")?, row.get("h")?),
         texture_index,
     })
 }
 
 fn sqlite_row_to_metadata(row: &Row) -> Result<ComicMetadata, Error> {
-    let last_time_opened: u64 = row.get(0)?;
-    let path: String = row.get(1)?;
-    let chunk_count: usize = row.get(2)?;
-    let last_seen_chunk: usize = row.get(3)?;
+    let last_time_opened: u64 = row.get("last_time_open")?;
+    let path: String = row.get("path")?;
+    let chunk_count: usize = row.get("chunk_count")?;
+    let last_seen_chunk: usize = row.get("last_chunk")?;
 
-    let path_object = Path::new(path.as_str());
-    let title = String::from(path_object.file_name().unwrap().to_str().unwrap());
-    let thumbnail: Vec<u8> = row.get(4).unwrap_or_default();
+    let title = match row.get::<_, Option<String>>("title").unwrap_or_default() {
+        Some(title) if !title.is_empty() => title,
+        _ => title_from_path(&path),
+    };
+    let thumbnail: Vec<u8> = row.get("icon").unwrap_or_default();
+    let reading_direction: i64 = row.get("reading_direction").unwrap_or_default();
+    let detection_params: Option<String> = row.get("detection_params").unwrap_or_default();
+    let page_order: i64 = row.get("page_order").unwrap_or_default();
+    let view_mode: i64 = row.get("view_mode").unwrap_or_default();
+    let page_fit: i64 = row.get("page_fit").unwrap_or_default();
 
     // eprintln!("ROW: {path} {title} {chunk_count} {last_seen_chunk}");
 
@@ -193,5 +1549,62 @@ fn sqlite_row_to_metadata(row: &Row) -> Result<ComicMetadata, Error> {
         } else {
             Some(thumbnail.to_vec())
         },
+        reading_direction: ReadingDirection::from_i64(reading_direction),
+        detection_params: detection_params.map(|it| DetectionParams::from_config_string(&it)),
+        page_order: PageOrder::from_i64(page_order),
+        favourite: row.get::<_, Option<bool>>("favourite")?.unwrap_or_default(),
+        view_mode: ViewMode::from_i64(view_mode),
+        page_fit: PageFit::from_i64(page_fit),
+        spread_shift: row
+            .get::<_, Option<bool>>("spread_shift")?
+            .unwrap_or_default(),
+        last_seen_page: row.get::<_, Option<usize>>("last_page")?.unwrap_or(0),
+        page_offset: row.get::<_, Option<f32>>("page_offset")?.unwrap_or(0.0),
+        page_count: row.get::<_, Option<usize>>("page_count")?.unwrap_or(0),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database_with(rows: &str) -> Database {
+        let db = Database::new(Path::new(":memory:"));
+        db.conn.execute_batch(rows).unwrap();
+        db
+    }
+
+    fn last_chunk(db: &Database, path: &str) -> Option<i64> {
+        db.conn
+            .query_row(
+                "SELECT last_chunk FROM Metadata WHERE path==?;",
+                [path],
+                |row| row.get(0),
+            )
+            .ok()
+    }
+
+    #[test]
+    fn relinks_a_moved_document_opened_without_progress() {
+        let mut db = database_with(
+            "INSERT INTO Metadata(last_time_open, path, chunk_count, last_chunk, fingerprint)
+                VALUES(1, '/missing/old.cbz', 10, 5, 'abc'), (2, '/missing/new.cbz', 10, 0, 'abc');",
+        );
+
+        assert!(db.relink_document("/missing/new.cbz", "abc"));
+        assert_eq!(last_chunk(&db, "/missing/new.cbz"), Some(5));
+        assert_eq!(last_chunk(&db, "/missing/old.cbz"), None);
+    }
+
+    #[test]
+    fn keeps_progress_made_at_the_new_path() {
+        let mut db = database_with(
+            "INSERT INTO Metadata(last_time_open, path, chunk_count, last_chunk, fingerprint)
+                VALUES(1, '/missing/old.cbz', 10, 5, 'abc'), (2, '/missing/new.cbz', 10, 0, 'abc');
+            INSERT INTO Bookmarks(path, texture_index, x, y) VALUES('/missing/new.cbz', 1, 0, 50);",
+        );
+
+        assert!(!db.relink_document("/missing/new.cbz", "abc"));
+        assert_eq!(last_chunk(&db, "/missing/old.cbz"), Some(5));
+    }
+}
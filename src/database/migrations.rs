@@ -0,0 +1,580 @@
+use rusqlite::{Connection, Error, Transaction};
+
+use super::{backfill_series, backfill_titles};
+
+//A single schema change, applied once to every database
+struct Migration {
+    name: &'static str,
+    apply: fn(&Transaction) -> Result<(), Error>,
+}
+
+//Schema changes in the order they were made, a database's user_version is how many it went through.
+//Only ever append to this list, a released step must never change.
+//Databases from before versioning have user_version 0 and some of these changes already,
+//so every step must work whether or not the tables and columns it adds exist
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "initial schema",
+        apply: initial_schema,
+    },
+    Migration {
+        name: "reading direction",
+        apply: reading_direction,
+    },
+    Migration {
+        name: "detection parameters",
+        apply: detection_params,
+    },
+    Migration {
+        name: "page records",
+        apply: page_records,
+    },
+    Migration {
+        name: "page order",
+        apply: page_order,
+    },
+    Migration {
+        name: "document titles",
+        apply: document_titles,
+    },
+    Migration {
+        name: "library folders",
+        apply: library_folders,
+    },
+    Migration {
+        name: "series",
+        apply: series,
+    },
+    Migration {
+        name: "bookmarks",
+        apply: bookmarks,
+    },
+    Migration {
+        name: "reading sessions",
+        apply: reading_sessions,
+    },
+    Migration {
+        name: "tags and collections",
+        apply: tags_and_collections,
+    },
+    Migration {
+        name: "document fingerprints",
+        apply: document_fingerprints,
+    },
+    Migration {
+        name: "view modes",
+        apply: view_modes,
+    },
+    Migration {
+        name: "spread shift",
+        apply: spread_shift,
+    },
+    Migration {
+        name: "page positions",
+        apply: page_positions,
+    },
+    Migration {
+        name: "page rotations",
+        apply: page_rotations,
+    },
+    Migration {
+        name: "page counts",
+        apply: page_counts,
+    },
+    Migration {
+        name: "side by side chunks",
+        apply: side_by_side_chunks,
+    },
+];
+
+//Version of the newest schema
+const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+//Bring a database up to the newest schema, each step is committed along with its version
+pub fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version = user_version(conn)?;
+
+    if version > SCHEMA_VERSION {
+        //Written by a newer version of the app, columns it added are left alone
+        eprintln!("Database schema version {version} is newer than {SCHEMA_VERSION}");
+        return Ok(());
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let next_version = index + 1;
+
+        let tx = conn
+            .transaction()
+            .map_err(|error| format!("Error starting migration: {error:?}"))?;
+
+        (migration.apply)(&tx)
+            .and_then(|_| tx.pragma_update(None, "user_version", next_version))
+            .and_then(|_| tx.commit())
+            .map_err(|error| {
+                format!(
+                    "Error migrating database to version {next_version} ({}): {error:?}",
+                    migration.name
+                )
+            })?;
+
+        log::info!(
+            "Migrated database to version {next_version} ({})",
+            migration.name
+        );
+    }
+
+    Ok(())
+}
+
+fn user_version(conn: &Connection) -> Result<usize, String> {
+    conn.query_row("PRAGMA user_version;", [], |row| row.get(0))
+        .map_err(|error| format!("Error reading database version: {error:?}"))
+}
+
+//Add a column unless the table already has it
+fn ensure_column(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), Error> {
+    if tx
+        .prepare(&format!("SELECT {column} FROM {table} LIMIT 0;"))
+        .is_ok()
+    {
+        return Ok(());
+    }
+
+    tx.execute(
+        &format!("ALTER TABLE {table} ADD COLUMN {column} {definition};"),
+        [],
+    )
+    .map(|_| ())
+}
+
+fn initial_schema(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Metadata(
+            last_time_open INTEGER,
+            path TEXT PRIMARY KEY,
+            chunk_count INTEGER,
+            last_chunk INTEGER,
+            icon BLOB
+        );
+
+        CREATE TABLE IF NOT EXISTS
+        Chunks(
+            path TEXT,
+            x INTEGER,
+            y INTEGER,
+            w INTEGER,
+            h INTEGER,
+            texture_index INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\", \"y\") ON CONFLICT IGNORE
+        );",
+    )
+}
+
+fn reading_direction(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "reading_direction", "INTEGER DEFAULT 0")
+}
+
+fn detection_params(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "detection_params", "TEXT")?;
+
+    //Chunks cached before these existed are never matched, and get replaced
+    ensure_column(tx, "Chunks", "params_hash", "INTEGER DEFAULT 0")?;
+    ensure_column(tx, "Chunks", "detector_version", "INTEGER DEFAULT 0")?;
+
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Settings(
+            key TEXT PRIMARY KEY,
+            value TEXT
+        );",
+    )
+}
+
+fn page_records(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Pages(
+            path TEXT,
+            texture_index INTEGER,
+            name TEXT,
+            size INTEGER,
+            modified INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\") ON CONFLICT REPLACE
+        );",
+    )
+}
+
+fn page_order(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "page_order", "INTEGER DEFAULT 0")
+}
+
+fn document_titles(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "title", "TEXT")?;
+    backfill_titles(tx);
+
+    Ok(())
+}
+
+fn library_folders(tx: &Transaction) -> Result<(), Error> {
+    //Documents a library scan couldn't find anymore, kept to remember their progress
+    ensure_column(tx, "Metadata", "missing", "INTEGER DEFAULT 0")?;
+
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        LibraryRoots(
+            path TEXT PRIMARY KEY
+        );",
+    )
+}
+
+fn series(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Series(
+            id INTEGER PRIMARY KEY,
+            key TEXT UNIQUE,
+            name TEXT
+        );",
+    )?;
+
+    //Volume numbers may have fractions, for specials like "5.5"
+    ensure_column(tx, "Metadata", "series_id", "INTEGER")?;
+    ensure_column(tx, "Metadata", "volume", "REAL")?;
+    backfill_series(tx);
+
+    Ok(())
+}
+
+fn bookmarks(tx: &Transaction) -> Result<(), Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Bookmarks(
+            path TEXT,
+            texture_index INTEGER,
+            x INTEGER,
+            y INTEGER,
+            w INTEGER,
+            h INTEGER,
+            note TEXT,
+            created INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\", \"x\", \"y\") ON CONFLICT REPLACE
+        );",
+    )
+}
+
+fn reading_sessions(tx: &Transaction) -> Result<(), Error> {
+    //Only ever appended to, one row each time a document is closed
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        ReadingSessions(
+            path TEXT,
+            started INTEGER,
+            ended INTEGER,
+            chunks_advanced INTEGER,
+            pages_seen INTEGER
+        );",
+    )
+}
+
+fn tags_and_collections(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "favourite", "INTEGER DEFAULT 0")?;
+
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        Tags(
+            id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE COLLATE NOCASE
+        );
+
+        CREATE TABLE IF NOT EXISTS
+        DocumentTags(
+            path TEXT,
+            tag_id INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"tag_id\") ON CONFLICT IGNORE
+        );
+
+        CREATE TABLE IF NOT EXISTS
+        Collections(
+            id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE
+        );
+
+        CREATE TABLE IF NOT EXISTS
+        CollectionDocuments(
+            collection_id INTEGER,
+            path TEXT,
+            added INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"collection_id\", \"path\") ON CONFLICT IGNORE
+        );",
+    )
+}
+
+fn document_fingerprints(tx: &Transaction) -> Result<(), Error> {
+    //Filled in the next time each document is opened
+    ensure_column(tx, "Metadata", "fingerprint", "TEXT")?;
+
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS MetadataFingerprint ON Metadata(fingerprint);")
+}
+
+fn view_modes(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "view_mode", "INTEGER DEFAULT 0")?;
+    ensure_column(tx, "Metadata", "page_fit", "INTEGER DEFAULT 0")
+}
+
+fn spread_shift(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "spread_shift", "INTEGER DEFAULT 0")
+}
+
+fn page_positions(tx: &Transaction) -> Result<(), Error> {
+    ensure_column(tx, "Metadata", "last_page", "INTEGER DEFAULT 0")?;
+    ensure_column(tx, "Metadata", "page_offset", "REAL DEFAULT 0")
+}
+
+fn page_rotations(tx: &Transaction) -> Result<(), Error> {
+    //Pages segmented before this were never turned
+    ensure_column(tx, "Pages", "rotation", "INTEGER DEFAULT 0")?;
+
+    //Kept apart from Pages, which is rewritten along with the chunk cache
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS
+        PageRotations(
+            path TEXT,
+            name TEXT,
+            rotation INTEGER,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"name\") ON CONFLICT REPLACE
+        );",
+    )
+}
+
+fn page_counts(tx: &Transaction) -> Result<(), Error> {
+    //Filled in the next time each document is closed, along with the page it was left on
+    ensure_column(tx, "Metadata", "page_count", "INTEGER DEFAULT 0")
+}
+
+fn side_by_side_chunks(tx: &Transaction) -> Result<(), Error> {
+    //Panels next to each other share their y, the old unique key kept only one of them.
+    //The constraint can't be changed in place, and the chunks cached until now were detected
+    //by an older DETECTOR_VERSION, so they are dropped along with the table
+    tx.execute_batch(
+        "
+        DROP TABLE IF EXISTS Chunks;
+
+        CREATE TABLE
+        Chunks(
+            path TEXT,
+            x INTEGER,
+            y INTEGER,
+            w INTEGER,
+            h INTEGER,
+            texture_index INTEGER,
+            params_hash INTEGER DEFAULT 0,
+            detector_version INTEGER DEFAULT 0,
+            CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\", \"x\", \"y\") ON CONFLICT IGNORE
+        );",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table_exists(conn: &Connection, table: &str) -> bool {
+        conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type=='table' AND name==?;",
+            [table],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap()
+            > 0
+    }
+
+    fn schema(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name;")
+            .unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.map(|it| it.unwrap()).collect()
+    }
+
+    #[test]
+    fn empty_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION);
+        for table in [
+            "Metadata",
+            "Chunks",
+            "Pages",
+            "Settings",
+            "Series",
+            "Bookmarks",
+            "ReadingSessions",
+            "Tags",
+            "Collections",
+            "PageRotations",
+        ] {
+            assert!(table_exists(&conn, table), "{table} is missing");
+        }
+    }
+
+    #[test]
+    fn legacy_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        //Schema written by versions from before migrations, with user_version 0
+        conn.execute_batch(
+            "
+            CREATE TABLE Metadata(
+                last_time_open INTEGER,
+                path TEXT PRIMARY KEY,
+                chunk_count INTEGER,
+                last_chunk INTEGER,
+                icon BLOB
+            );
+
+            CREATE TABLE Chunks(
+                path TEXT,
+                x INTEGER,
+                y INTEGER,
+                w INTEGER,
+                h INTEGER,
+                texture_index INTEGER,
+                CONSTRAINT \"uniq\" UNIQUE (\"path\", \"texture_index\", \"y\") ON CONFLICT IGNORE
+            );
+
+            INSERT INTO Metadata VALUES(1650000000, '/comics/Berserk/Berserk v02.cbz', 120, 42, x'89504e47');
+            INSERT INTO Chunks VALUES('/comics/Berserk/Berserk v02.cbz', 0, 0, 100, 200, 0);",
+        )
+        .unwrap();
+
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION);
+
+        let (last_time_open, chunk_count, last_chunk, icon, title, volume): (
+            u64,
+            usize,
+            usize,
+            Vec<u8>,
+            String,
+            f64,
+        ) = conn
+            .query_row(
+                "SELECT last_time_open, chunk_count, last_chunk, icon, title, volume FROM Metadata WHERE path==?;",
+                ["/comics/Berserk/Berserk v02.cbz"],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap();
+
+        assert_eq!(last_time_open, 1650000000);
+        assert_eq!(chunk_count, 120);
+        assert_eq!(last_chunk, 42);
+        assert_eq!(icon, vec![0x89, 0x50, 0x4e, 0x47]);
+        assert_eq!(title, "Berserk v02.cbz");
+        assert_eq!(volume, 2.0);
+
+        //Columns added along the way get their defaults
+        let (reading_direction, view_mode, missing, page_count, fingerprint): (
+            i64,
+            i64,
+            i64,
+            i64,
+            Option<String>,
+        ) = conn
+            .query_row(
+                "SELECT reading_direction, view_mode, missing, page_count, fingerprint FROM Metadata;",
+                [],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            (
+                reading_direction,
+                view_mode,
+                missing,
+                page_count,
+                fingerprint
+            ),
+            (0, 0, 0, 0, None)
+        );
+
+        //Chunks from the old detector are dropped, and side by side ones are kept apart now
+        conn.execute_batch(
+            "
+            INSERT INTO Chunks(path, x, y, w, h, texture_index) VALUES('a', 0, 0, 10, 10, 0);
+            INSERT INTO Chunks(path, x, y, w, h, texture_index) VALUES('a', 20, 0, 10, 10, 0);",
+        )
+        .unwrap();
+        let chunks: i64 = conn
+            .query_row("SELECT COUNT(*) FROM Chunks;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(chunks, 2);
+    }
+
+    #[test]
+    fn migrating_twice() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO Metadata(last_time_open, path, chunk_count, last_chunk) VALUES(1, 'a', 10, 5);",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO Chunks(path, x, y, w, h, texture_index) VALUES('a', 0, 0, 10, 10, 0);",
+            [],
+        )
+        .unwrap();
+
+        let before = schema(&conn);
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(user_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert_eq!(schema(&conn), before);
+
+        let rows: (i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM Metadata), (SELECT COUNT(*) FROM Chunks);",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(rows, (1, 1));
+    }
+}
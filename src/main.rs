@@ -4,17 +4,25 @@ use std::{borrow::BorrowMut, fs::File};
 
 use application::Application;
 use log::*;
+use paths::AppPaths;
 use raylib::prelude::*;
 use simplelog::{ColorChoice, Config, LevelFilter, TermLogger, TerminalMode, WriteLogger};
 
 pub mod application;
+#[cfg(feature = "unarr")]
 pub mod archive;
 pub mod chunkprovider;
 pub mod database;
+pub mod paths;
 pub mod processing;
+pub mod scanner;
+pub mod series;
 pub mod structs;
 pub mod traits;
+pub mod transfer;
+#[cfg(feature = "unarr")]
 pub mod unarr;
+pub mod ziparchive;
 
 //Constants and info for the whole application
 const APP_TITLE: &str = "Manga Viewer";
@@ -30,30 +38,35 @@ fn load_font(rl: &mut RaylibHandle, thread: &RaylibThread, size: i32) -> Result<
 }
 
 fn main() {
-    std::fs::write("/tmp/log", "Starting").unwrap();
-
-    match simplelog::CombinedLogger::init(vec![
-        TermLogger::new(
-            LevelFilter::Warn,
-            Config::default(),
-            TerminalMode::Mixed,
-            ColorChoice::Auto,
-        ),
-        WriteLogger::new(
-            LevelFilter::Info,
-            Config::default(),
-            File::create("manga-viewer.log").unwrap(),
-        ),
-    ]) {
-        Ok(_) => {
-            std::fs::write("/tmp/log.txt", format!("Logs created!")).unwrap();
-        }
-        Err(error) => {
-            std::fs::write("/tmp/log.txt", format!("Error creating logs: {error}!")).unwrap();
-        }
+    let paths = AppPaths::resolve();
+
+    let mut loggers: Vec<Box<dyn simplelog::SharedLogger>> = vec![TermLogger::new(
+        LevelFilter::Warn,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )];
+
+    //Keep running with terminal logs only if the log file can't be written
+    match File::create(paths.log()) {
+        Ok(file) => loggers.push(WriteLogger::new(LevelFilter::Info, Config::default(), file)),
+        Err(error) => eprintln!("Error creating {}: {error}", paths.log().display()),
+    }
+
+    if let Err(error) = simplelog::CombinedLogger::init(loggers) {
+        eprintln!("Error creating logs: {error}");
     }
 
     debug!("Running");
+    info!(
+        "Data in {}, cache in {}, config in {}{}",
+        paths.data.display(),
+        paths.cache.display(),
+        paths.config.display(),
+        if paths.portable { " (portable)" } else { "" }
+    );
+
+    paths.adopt_legacy_database();
 
     //Initialze RayGUI
     let (mut rl, thread) = init()
@@ -118,7 +131,7 @@ fn main() {
 
     debug!("Creating application");
     //Instantiate the application
-    let mut app: Application = Application::new(&mut rl, &thread, logo_texture);
+    let mut app: Application = Application::new(&mut rl, &thread, logo_texture, &paths);
 
     //Padding for the main UI
     const PADDING: f32 = 10.0;
@@ -146,6 +159,9 @@ fn main() {
     #[cfg(target_os = "macos")]
     const MOD_KEY: KeyboardKey = KeyboardKey::KEY_LEFT_SUPER;
 
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    const MOD_KEY: KeyboardKey = KeyboardKey::KEY_LEFT_CONTROL;
+
     debug!("Starting main loop");
     //RayLib's mainloop
     while !rl.borrow_mut().window_should_close() {
@@ -216,6 +232,6 @@ fn main() {
         );
     }
 
-    //Unload current provider, so metadata gets saved on app quit
-    app.provider.unload();
+    //Close the current document, so metadata and the reading session get saved on app quit
+    app.close_document();
 }
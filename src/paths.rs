@@ -0,0 +1,163 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+//Folder created inside the per-user data, cache and config folders
+const APP_FOLDER: &str = "manga-viewer";
+
+//Keeps everything next to the binary when set, or when a file with this name is there
+const PORTABLE_VARIABLE: &str = "MANGA_VIEWER_PORTABLE";
+const PORTABLE_MARKER: &str = "portable";
+
+//Folders the app reads and writes, each can be overridden through the environment
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    //Metadata database, kept across updates
+    pub data: PathBuf,
+    //Logs, safe to delete
+    pub cache: PathBuf,
+    //Settings edited by the user
+    pub config: PathBuf,
+    pub portable: bool,
+}
+
+impl AppPaths {
+    //Resolve every folder and create the missing ones
+    pub fn resolve() -> Self {
+        let portable_dir = portable_dir();
+        let portable = portable_dir.is_some();
+
+        let base = |override_variable: &str, platform: fn() -> Option<PathBuf>| -> PathBuf {
+            if let Some(path) = env_path(override_variable) {
+                return path;
+            }
+
+            match &portable_dir {
+                Some(dir) => dir.clone(),
+                //Nowhere better to go, fall back on the working directory like older versions
+                None => platform()
+                    .map(|it| it.join(APP_FOLDER))
+                    .unwrap_or_else(|| PathBuf::from(".")),
+            }
+        };
+
+        let paths = Self {
+            data: base("MANGA_VIEWER_DATA_DIR", data_home),
+            cache: base("MANGA_VIEWER_CACHE_DIR", cache_home),
+            config: base("MANGA_VIEWER_CONFIG_DIR", config_home),
+            portable,
+        };
+
+        for dir in [&paths.data, &paths.cache, &paths.config] {
+            if let Err(error) = std::fs::create_dir_all(dir) {
+                eprintln!("Error creating {}: {error}", dir.display());
+            }
+        }
+
+        paths
+    }
+
+    pub fn database(&self) -> PathBuf {
+        self.data.join("metadata.sqlite3")
+    }
+
+    pub fn log(&self) -> PathBuf {
+        self.cache.join("manga-viewer.log")
+    }
+
+    //Older versions kept the database in the working directory, move it over the first time
+    pub fn adopt_legacy_database(&self) {
+        let legacy = PathBuf::from("metadata.sqlite3");
+        let database = self.database();
+
+        if database.exists() || !legacy.is_file() {
+            return;
+        }
+
+        let result = std::fs::rename(&legacy, &database)
+            //Renaming fails across file systems
+            .or_else(|_| std::fs::copy(&legacy, &database).map(|_| ()));
+
+        match result {
+            Ok(_) => log::info!(
+                "Moved database from {} to {}",
+                legacy.display(),
+                database.display()
+            ),
+            Err(error) => log::error!("Error moving database to {}: {error}", database.display()),
+        }
+    }
+}
+
+//Folder of the binary if running in portable mode
+fn portable_dir() -> Option<PathBuf> {
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|it| it.parent().map(Path::to_path_buf))?;
+
+    let enabled = match env::var(PORTABLE_VARIABLE) {
+        Ok(value) => !matches!(value.as_str(), "" | "0" | "false"),
+        Err(_) => exe_dir.join(PORTABLE_MARKER).exists(),
+    };
+
+    enabled.then_some(exe_dir)
+}
+
+//Path from an environment variable, relative ones are ignored like the XDG spec asks
+fn env_path(variable: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|it| it.is_absolute())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn home() -> Option<PathBuf> {
+    env_path("HOME")
+}
+
+#[cfg(target_os = "windows")]
+fn data_home() -> Option<PathBuf> {
+    env_path("APPDATA")
+}
+
+#[cfg(target_os = "windows")]
+fn cache_home() -> Option<PathBuf> {
+    env_path("LOCALAPPDATA")
+}
+
+#[cfg(target_os = "windows")]
+fn config_home() -> Option<PathBuf> {
+    env_path("APPDATA")
+}
+
+#[cfg(target_os = "macos")]
+fn data_home() -> Option<PathBuf> {
+    home().map(|it| it.join("Library/Application Support"))
+}
+
+#[cfg(target_os = "macos")]
+fn cache_home() -> Option<PathBuf> {
+    home().map(|it| it.join("Library/Caches"))
+}
+
+#[cfg(target_os = "macos")]
+fn config_home() -> Option<PathBuf> {
+    home().map(|it| it.join("Library/Preferences"))
+}
+
+//XDG base directories
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn data_home() -> Option<PathBuf> {
+    env_path("XDG_DATA_HOME").or_else(|| home().map(|it| it.join(".local/share")))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn cache_home() -> Option<PathBuf> {
+    env_path("XDG_CACHE_HOME").or_else(|| home().map(|it| it.join(".cache")))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn config_home() -> Option<PathBuf> {
+    env_path("XDG_CONFIG_HOME").or_else(|| home().map(|it| it.join(".config")))
+}
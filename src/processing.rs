@@ -1,100 +1,496 @@
-use std::path::Path;
+use std::{cmp::Ordering, ffi::c_void, fs, path::Path, time::UNIX_EPOCH};
 
-use crate::{
-    archive::{ArEntryInfo, Archive},
-    structs::Chunk,
-};
+#[cfg(feature = "unarr")]
+use crate::archive::{ArEntryInfo, Archive};
+use crate::structs::{Chunk, DetectionParams, PageOrder, ReadingDirection};
+use image::{codecs::jpeg::JpegEncoder, ColorType, ImageFormat};
+use raylib::consts::PixelFormat;
 use raylib::math::Rectangle;
-use raylib::prelude::Image;
+use raylib::prelude::{Color, Image};
 
-//Get chunk metadata from image
-#[allow(unused)]
-pub fn get_chunks_from_image(image: &mut Image) -> Vec<Chunk> {
-    //How many white strips counts as a chunk separator
-    const WHITE_STRIP_THRESHOLD: usize = 5;
+//Page formats decoded by raylib itself
+const RAYLIB_PAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "tga", "gif"];
 
-    //Minimal height a chunk must have to be recognized as such
-    const MIN_CHUNK_HEIGHT: usize = WHITE_STRIP_THRESHOLD + 1;
+//Returns the lowercase extension of a file or entry name
+pub fn extension_of(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .map(|it| it.to_string_lossy().to_lowercase())
+}
 
-    //Set image format to 8bit grayscale, to decrease processing costs
-    image.set_format(raylib::consts::PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE);
+fn load_page_with_image_crate(name: &str, data: &[u8], format: ImageFormat) -> Option<Image> {
+    let decoded = match image::load_from_memory_with_format(data, format) {
+        Ok(it) => it.into_rgba8(),
+        Err(error) => {
+            log::error!("Error decoding image '{name}': {error}");
+            return None;
+        }
+    };
 
-    //Get image's color data (w*h*depth bytes)
-    let colors = image.get_image_data();
+    let (width, height) = decoded.dimensions();
+    let pixels = decoded.into_raw();
 
-    //Bitmap of horizontal white strips
-    let mut white_strip_map = Vec::<bool>::new();
+    unsafe {
+        //raylib frees the pixels when the image is dropped, so they must come from its allocator
+        let buffer = raylib::ffi::MemAlloc(pixels.len() as i32) as *mut u8;
+        if buffer.is_null() {
+            log::error!("Couldn't allocate memory for '{name}'");
+            return None;
+        }
+        std::ptr::copy_nonoverlapping(pixels.as_ptr(), buffer, pixels.len());
+
+        Some(Image::from_raw(raylib::ffi::Image {
+            data: buffer as *mut c_void,
+            width: width as i32,
+            height: height as i32,
+            mipmaps: 1,
+            //The bindings spell this variant with a doubled prefix
+            format: PixelFormat::PIXELFORMAT_PIXELFORMAT_UNCOMPRESSED_R8G8B8A8 as i32,
+        }))
+    }
+}
 
-    //Iterate vertically over the whole image
-    for y in 0..image.height {
-        //The strips are marked as white by default
-        let mut white = true;
+//Encode a JPEG thumbnail of a page, resized to the given size
+pub fn make_thumbnail(image: &Image, width: i32, height: i32) -> Option<Vec<u8>> {
+    let mut thumbnail = image.clone();
+    thumbnail.resize(width, height);
 
-        //Iterate over the whole strip until non-white pixels are found
-        for x in 0..image.width {
-            //Transform x,y coordinates to linear pixel offset
-            let offset: usize = (x + y * image.width).try_into().unwrap();
+    let pixels: Vec<u8> = thumbnail
+        .get_image_data()
+        .iter()
+        .flat_map(|color| [color.r, color.g, color.b])
+        .collect();
 
-            //Compare the color and a white threshold
-            if colors[offset].r < 210 {
-                white = false;
-                break;
+    let mut data = Vec::new();
+    match JpegEncoder::new_with_quality(&mut data, 85).encode(
+        &pixels,
+        width as u32,
+        height as u32,
+        ColorType::Rgb8,
+    ) {
+        Ok(_) => Some(data),
+        Err(error) => {
+            log::error!("Error encoding thumbnail: {error}");
+            None
+        }
+    }
+}
+
+//Last modification time of a file in seconds since the epoch, 0 if it can't be read
+pub fn modified_time_of(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|it| it.modified())
+        .ok()
+        .and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |it| it.as_secs())
+}
+
+//Compare names case-insensitively, taking runs of digits as numbers (page2 < page10)
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut x_digits = String::new();
+                while let Some(digit) = a_chars.next_if(|it| it.is_ascii_digit()) {
+                    x_digits.push(digit);
+                }
+
+                let mut y_digits = String::new();
+                while let Some(digit) = b_chars.next_if(|it| it.is_ascii_digit()) {
+                    y_digits.push(digit);
+                }
+
+                //Compare by value without parsing, so long runs can't overflow
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
             }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+//Sort pages given a function returning their name and modification time
+pub fn sort_pages<T>(pages: &mut [T], order: PageOrder, key: impl Fn(&T) -> (&str, u64)) {
+    match order {
+        PageOrder::Natural => pages.sort_by(|a, b| natural_cmp(key(a).0, key(b).0)),
+        PageOrder::Reverse => pages.sort_by(|a, b| natural_cmp(key(b).0, key(a).0)),
+        PageOrder::Modified => pages.sort_by(|a, b| {
+            let (a_name, a_modified) = key(a);
+            let (b_name, b_modified) = key(b);
+
+            a_modified
+                .cmp(&b_modified)
+                .then_with(|| natural_cmp(a_name, b_name))
+        }),
+    }
+}
+
+//Page formats raylib can't decode, handled by the image crate instead
+fn rust_page_format(extension: &str) -> Option<ImageFormat> {
+    match extension {
+        "webp" => Some(ImageFormat::WebP),
+        "qoi" => Some(ImageFormat::Qoi),
+        #[cfg(feature = "avif")]
+        "avif" => Some(ImageFormat::Avif),
+        _ => None,
+    }
+}
+
+//Whether a file or entry name looks like a page image
+pub fn is_page_file(name: &str) -> bool {
+    match extension_of(name) {
+        Some(extension) => {
+            RAYLIB_PAGE_EXTENSIONS.contains(&extension.as_str())
+                || rust_page_format(&extension).is_some()
         }
+        None => false,
+    }
+}
 
-        //Push the not-white status of the line
-        white_strip_map.push(white);
+//Decode a page file, using its name to guess the format
+pub fn load_page_from_file(path: &str) -> Option<Image> {
+    match fs::read(path) {
+        Ok(data) => load_page_from_memory(path, &data),
+        Err(error) => {
+            log::error!("Error reading '{path}': {error}");
+            None
+        }
     }
+}
 
-    //Push an extra white line, so the last chunk is always added to resulting Vec
-    white_strip_map.push(true);
+//Decode a page stored in memory, using its name to guess the format
+pub fn load_page_from_memory(name: &str, data: &Vec<u8>) -> Option<Image> {
+    let extension = extension_of(name).unwrap_or_default();
 
-    //Chunk vector
+    if let Some(format) = rust_page_format(&extension) {
+        return load_page_with_image_crate(name, data, format);
+    }
+
+    match Image::load_image_from_mem(format!(".{extension}").as_str(), data, data.len() as i32) {
+        Ok(it) => Some(it),
+        Err(error) => {
+            log::error!("Error loading image '{name}': {error}");
+            None
+        }
+    }
+}
+
+//Bumped whenever the detector changes, so chunks cached by older versions get discarded
+pub const DETECTOR_VERSION: i64 = 4;
+
+//How many nested cuts are tried before giving up on a region
+const MAX_CUT_DEPTH: usize = 8;
+
+//Width of the page border sampled to estimate the gutter color, in pixels
+const BORDER_SAMPLE_SIZE: usize = 4;
+
+//Bits kept per channel when looking for the most common border color
+const BORDER_COLOR_BITS: u8 = 4;
+
+//Direction in which a region gets split
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CutAxis {
+    //Split using horizontal gutter strips
+    Rows,
+    //Split using vertical gutter strips
+    Columns,
+}
+
+impl CutAxis {
+    fn other(self) -> Self {
+        match self {
+            CutAxis::Rows => CutAxis::Columns,
+            CutAxis::Columns => CutAxis::Rows,
+        }
+    }
+}
+
+//A rectangular region of the page, in pixels
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+//Bitmap of gutter pixels for the whole page, the order its panels are read in and how to cut them
+struct GutterMap {
+    width: usize,
+    pixels: Vec<bool>,
+    direction: ReadingDirection,
+    params: DetectionParams,
+}
+
+impl GutterMap {
+    fn is_gutter(&self, x: usize, y: usize) -> bool {
+        self.pixels[x + y * self.width]
+    }
+}
+
+//Get chunk metadata from image
+pub fn get_chunks_from_image(
+    image: &mut Image,
+    direction: ReadingDirection,
+    params: &DetectionParams,
+) -> Vec<Chunk> {
+    //Get image's color data, one RGBA color per pixel
+    let colors = image.get_image_data();
+
+    chunks_from_colors(
+        &colors,
+        image.width as usize,
+        image.height as usize,
+        direction,
+        params,
+    )
+}
+
+//Chunks of a page given as one color per pixel, row by row
+fn chunks_from_colors(
+    colors: &[Color],
+    width: usize,
+    height: usize,
+    direction: ReadingDirection,
+    params: &DetectionParams,
+) -> Vec<Chunk> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    //Gutters are whatever looks like the page's background, be it white, black or tinted
+    let background = estimate_background_color(colors, width, height);
+
+    //Compare every pixel against the background color once
+    let map = GutterMap {
+        width,
+        pixels: colors
+            .iter()
+            .map(|color| is_close_to(color, &background, params.gutter_color_tolerance))
+            .collect(),
+        direction,
+        params: *params,
+    };
+
+    let page = Region {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+
+    //Vertical strips are only split in rows, keeping the page's full width
+    if direction == ReadingDirection::Vertical {
+        return split_region(&map, page, CutAxis::Rows)
+            .into_iter()
+            .map(chunk_from_region)
+            .collect();
+    }
+
+    //Panels are read in rows first, then columns inside every row (XY-cut)
     let mut chunks = Vec::<Chunk>::new();
+    xy_cut(&map, page, CutAxis::Rows, false, 0, &mut chunks);
 
-    //Initialize state-machine
-    let mut last = white_strip_map[0];
-    let mut last_chunk_start = 0;
-
-    //Starts in 1 cause first item was taken as initial status anyways
-    let mut y = 1;
-
-    while y < white_strip_map.len() {
-        //Act only at borders
-        if last != white_strip_map[y] {
-            //If a chunk end was found
-            if white_strip_map[y] {
-                //The chunk's height is calculated
-                let height = y - last_chunk_start;
-                chunks.push(Chunk {
-                    rect: Rectangle::new(
-                        0.0,
-                        last_chunk_start as f32,
-                        image.width as f32,
-                        height as f32,
-                    ),
-                    texture_index: 0,
-                });
-                last_chunk_start = y + 1;
-            } else {
-                //Register that a chunk started here
-                last_chunk_start = y;
+    chunks
+}
+
+//Most common color along the page's borders, where gutters and margins usually are
+fn estimate_background_color(colors: &[Color], width: usize, height: usize) -> Color {
+    let band = BORDER_SAMPLE_SIZE.min(width / 2).min(height / 2).max(1);
+    let is_border = |x: usize, y: usize| {
+        x < band || y < band || x >= width.saturating_sub(band) || y >= height.saturating_sub(band)
+    };
+
+    //Group similar colors together, so noise doesn't split the vote
+    let shift = 8 - BORDER_COLOR_BITS;
+    let bucket_of = |color: &Color| {
+        ((color.r >> shift) as usize) << (2 * BORDER_COLOR_BITS)
+            | ((color.g >> shift) as usize) << BORDER_COLOR_BITS
+            | (color.b >> shift) as usize
+    };
+
+    //Sum of every channel and pixel count, per bucket
+    let mut buckets = vec![(0usize, 0usize, 0usize, 0usize); 1 << (3 * BORDER_COLOR_BITS)];
+
+    for y in 0..height {
+        for x in 0..width {
+            if !is_border(x, y) {
+                continue;
             }
+
+            let color = &colors[x + y * width];
+            let bucket = &mut buckets[bucket_of(color)];
+            bucket.0 += color.r as usize;
+            bucket.1 += color.g as usize;
+            bucket.2 += color.b as usize;
+            bucket.3 += 1;
+        }
+    }
+
+    //Average the colors of the most voted bucket
+    match buckets.iter().max_by_key(|bucket| bucket.3) {
+        Some((r, g, b, count)) if *count > 0 => {
+            Color::new((r / count) as u8, (g / count) as u8, (b / count) as u8, 255)
         }
+        _ => Color::WHITE,
+    }
+}
+
+fn is_close_to(color: &Color, background: &Color, tolerance: u8) -> bool {
+    color.r.abs_diff(background.r) <= tolerance
+        && color.g.abs_diff(background.g) <= tolerance
+        && color.b.abs_diff(background.b) <= tolerance
+}
+
+//Recursively split a region along alternating axes, pushing the leaves into chunks
+fn xy_cut(
+    map: &GutterMap,
+    region: Region,
+    axis: CutAxis,
+    other_axis_tried: bool,
+    depth: usize,
+    chunks: &mut Vec<Chunk>,
+) {
+    //Regions nested too deep are taken as they are
+    if depth > MAX_CUT_DEPTH {
+        chunks.push(chunk_from_region(region));
+        return;
+    }
+
+    let segments = split_region(map, region, axis);
+
+    //A blank region has no chunks at all
+    if segments.is_empty() {
+        return;
+    }
+
+    //Can't split along this axis, try the other one before calling it a leaf
+    if segments.len() == 1 {
+        if other_axis_tried {
+            chunks.push(chunk_from_region(segments[0]));
+        } else {
+            xy_cut(map, segments[0], axis.other(), true, depth + 1, chunks);
+        }
+        return;
+    }
 
-        //Update state-machine
-        last = white_strip_map[y];
+    let mut segments = segments;
 
-        //Increment y
-        y += 1;
+    //Columns are read from right to left in manga
+    if axis == CutAxis::Columns && map.direction == ReadingDirection::RightToLeft {
+        segments.reverse();
     }
 
-    return chunks
+    for segment in segments {
+        xy_cut(map, segment, axis.other(), false, depth + 1, chunks);
+    }
+}
+
+fn chunk_from_region(region: Region) -> Chunk {
+    Chunk {
+        rect: Rectangle::new(
+            region.x as f32,
+            region.y as f32,
+            region.width as f32,
+            region.height as f32,
+        ),
+        texture_index: 0,
+    }
+}
+
+//Split a region along one axis, returning its non-gutter parts trimmed and in reading order
+fn split_region(map: &GutterMap, region: Region, axis: CutAxis) -> Vec<Region> {
+    let (length, cross_length, min_length) = match axis {
+        CutAxis::Rows => (region.height, region.width, map.params.min_chunk_height),
+        CutAxis::Columns => (region.width, region.height, map.params.min_chunk_width),
+    };
+
+    //How many noisy pixels a strip can have and still be a gutter
+    let max_noise = cross_length * map.params.max_noise_percent / 100;
+
+    //Whether the i-th strip along the axis is (almost) entirely gutter
+    let strip_is_gutter = |i: usize| {
+        (0..cross_length)
+            .filter(|&j| match axis {
+                CutAxis::Rows => !map.is_gutter(region.x + j, region.y + i),
+                CutAxis::Columns => !map.is_gutter(region.x + i, region.y + j),
+            })
+            .nth(max_noise)
+            .is_none()
+    };
+
+    //Ranges of non-gutter strips, as (start, length)
+    let mut ranges = Vec::<(usize, usize)>::new();
+
+    //Initialize state-machine
+    let mut range_start: Option<usize> = None;
+    let mut last_content = 0;
+    let mut gutter_run = 0;
+
+    for i in 0..length {
+        if strip_is_gutter(i) {
+            gutter_run += 1;
+
+            //Enough gutter strips in a row close the current range
+            if gutter_run >= map.params.gutter_strip_threshold {
+                if let Some(start) = range_start.take() {
+                    ranges.push((start, last_content + 1 - start));
+                }
+            }
+        } else {
+            gutter_run = 0;
+            range_start.get_or_insert(i);
+            last_content = i;
+        }
+    }
+
+    //Close the last range, if it reaches the region's end
+    if let Some(start) = range_start {
+        ranges.push((start, last_content + 1 - start));
+    }
+
+    ranges
         .into_iter()
-        .filter(|x| x.rect.height > (MIN_CHUNK_HEIGHT as f32))
-        .collect();
+        .filter(|(_, range_length)| *range_length > min_length)
+        .map(|(start, range_length)| match axis {
+            CutAxis::Rows => Region {
+                y: region.y + start,
+                height: range_length,
+                ..region
+            },
+            CutAxis::Columns => Region {
+                x: region.x + start,
+                width: range_length,
+                ..region
+            },
+        })
+        .collect()
 }
 
+#[cfg(feature = "unarr")]
 #[allow(unused)]
 pub fn process_page<'a>(archive: Archive, entry: &ArEntryInfo) -> Vec<Chunk> {
     let data = archive
@@ -110,7 +506,11 @@ pub fn process_page<'a>(archive: Archive, entry: &ArEntryInfo) -> Vec<Chunk> {
         data.len().try_into().unwrap(),
     ) {
         Ok(mut image) => {
-            let chunks = get_chunks_from_image(&mut image);
+            let chunks = get_chunks_from_image(
+                &mut image,
+                ReadingDirection::default(),
+                &DetectionParams::default(),
+            );
             return chunks;
         }
         Err(_) => {
@@ -170,3 +570,247 @@ pub fn process_page<'a>(archive: Archive, entry: &ArEntryInfo) -> Vec<Chunk> {
 //         None => return,
 //     };
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //White page of the given size with black panels, as (x, y, width, height)
+    fn page(width: usize, height: usize, panels: &[(usize, usize, usize, usize)]) -> Vec<Color> {
+        let mut colors = vec![Color::WHITE; width * height];
+        for &(x, y, panel_width, panel_height) in panels {
+            for row in y..y + panel_height {
+                colors[row * width + x..row * width + x + panel_width].fill(Color::BLACK);
+            }
+        }
+        colors
+    }
+
+    fn rects(chunks: &[Chunk]) -> Vec<(usize, usize, usize, usize)> {
+        chunks
+            .iter()
+            .map(|it| {
+                let rect = it.rect;
+                (
+                    rect.x as usize,
+                    rect.y as usize,
+                    rect.width as usize,
+                    rect.height as usize,
+                )
+            })
+            .collect()
+    }
+
+    fn detect(
+        colors: &[Color],
+        width: usize,
+        height: usize,
+        direction: ReadingDirection,
+    ) -> Vec<(usize, usize, usize, usize)> {
+        rects(&chunks_from_colors(
+            colors,
+            width,
+            height,
+            direction,
+            &DetectionParams::default(),
+        ))
+    }
+
+    #[test]
+    fn grid_of_panels() {
+        let panels = [
+            (5, 5, 40, 40),
+            (55, 5, 40, 40),
+            (5, 55, 40, 40),
+            (55, 55, 40, 40),
+        ];
+        let colors = page(100, 100, &panels);
+
+        assert_eq!(
+            detect(&colors, 100, 100, ReadingDirection::LeftToRight),
+            panels
+        );
+
+        //Each row is read from its right panel in manga
+        assert_eq!(
+            detect(&colors, 100, 100, ReadingDirection::RightToLeft),
+            [panels[1], panels[0], panels[3], panels[2]]
+        );
+    }
+
+    #[test]
+    fn side_by_side_panels() {
+        //Same y, they can only be told apart by x
+        let panels = [(5, 10, 40, 80), (55, 10, 40, 80)];
+        let colors = page(100, 100, &panels);
+
+        assert_eq!(
+            detect(&colors, 100, 100, ReadingDirection::LeftToRight),
+            panels
+        );
+    }
+
+    #[test]
+    fn page_without_panels() {
+        let colors = page(100, 100, &[]);
+
+        assert!(detect(&colors, 100, 100, ReadingDirection::LeftToRight).is_empty());
+        assert!(detect(&colors, 100, 100, ReadingDirection::Vertical).is_empty());
+        assert!(detect(&[], 0, 0, ReadingDirection::LeftToRight).is_empty());
+    }
+
+    #[test]
+    fn one_pixel_wide_page() {
+        let colors = page(1, 60, &[(0, 20, 1, 20)]);
+
+        //Narrower than min_chunk_width, unless strips are only split in rows
+        assert!(detect(&colors, 1, 60, ReadingDirection::LeftToRight).is_empty());
+        assert_eq!(
+            detect(&colors, 1, 60, ReadingDirection::Vertical),
+            [(0, 20, 1, 20)]
+        );
+    }
+
+    #[test]
+    fn dark_border_page() {
+        //Light panels on a black page, the gutters are black too
+        let panels = [(5, 5, 40, 40), (55, 5, 40, 40), (5, 55, 90, 40)];
+        let colors: Vec<Color> = page(100, 100, &panels)
+            .into_iter()
+            .map(|color| {
+                if color == Color::WHITE {
+                    Color::new(12, 10, 14, 255)
+                } else {
+                    Color::new(210, 200, 190, 255)
+                }
+            })
+            .collect();
+
+        let background = estimate_background_color(&colors, 100, 100);
+        assert_eq!((background.r, background.g, background.b), (12, 10, 14));
+
+        assert_eq!(
+            detect(&colors, 100, 100, ReadingDirection::LeftToRight),
+            panels
+        );
+    }
+
+    #[test]
+    fn noisy_gutters() {
+        let panels = [
+            (10, 10, 85, 85),
+            (105, 10, 85, 85),
+            (10, 105, 85, 85),
+            (105, 105, 85, 85),
+        ];
+        let mut colors = page(200, 200, &panels);
+
+        //Paper that isn't quite white, within the color tolerance
+        for (i, color) in colors.iter_mut().enumerate() {
+            if *color == Color::WHITE {
+                let shade = 255 - ((i * 37) % 30) as u8;
+                *color = Color::new(shade, shade - 5, shade, 255);
+            }
+        }
+
+        //A speck in every gutter strip, but no more than max_noise_percent of it
+        for k in 0..10 {
+            colors[(95 + k) + (10 + k * 18) * 200] = Color::BLACK;
+            colors[(10 + k * 18) + (95 + k) * 200] = Color::BLACK;
+        }
+
+        let background = estimate_background_color(&colors, 200, 200);
+        assert!(is_close_to(&background, &Color::WHITE, 30));
+
+        assert_eq!(
+            detect(&colors, 200, 200, ReadingDirection::LeftToRight),
+            panels
+        );
+    }
+
+    fn naturally_sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|it| it.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("2", "10"), Ordering::Less);
+        assert_eq!(natural_cmp("page10.png", "page9.png"), Ordering::Greater);
+        assert_eq!(
+            naturally_sorted(&["c10p2.jpg", "c2p10.jpg", "c2p9.jpg", "c10p1.jpg"]),
+            ["c2p9.jpg", "c2p10.jpg", "c10p1.jpg", "c10p2.jpg"]
+        );
+
+        //Longer than any integer type
+        assert_eq!(
+            natural_cmp("99999999999999999999999", "100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(
+            naturally_sorted(&["010.png", "9.png", "001.png", "02.png"]),
+            ["001.png", "02.png", "9.png", "010.png"]
+        );
+
+        //Same value, told apart by their text so the order is still total
+        assert_eq!(natural_cmp("007", "7"), Ordering::Less);
+        assert_eq!(natural_cmp("7", "007"), Ordering::Greater);
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(
+            naturally_sorted(&["b.png", "Page10.png", "A.png", "page2.png"]),
+            ["A.png", "b.png", "page2.png", "Page10.png"]
+        );
+        assert_ne!(natural_cmp("Page", "page"), Ordering::Equal);
+    }
+
+    #[test]
+    fn non_ascii_names() {
+        assert_eq!(
+            naturally_sorted(&["ページ10.jpg", "ページ2.jpg", "ページ1.jpg"]),
+            ["ページ1.jpg", "ページ2.jpg", "ページ10.jpg"]
+        );
+        assert_eq!(natural_cmp("Écran 2", "écran 10"), Ordering::Less);
+
+        //Digits other than ASCII are compared as text
+        assert_eq!(natural_cmp("１０", "２"), Ordering::Less);
+    }
+
+    #[test]
+    fn page_orders() {
+        let pages = [("10.png", 3), ("2.png", 3), ("1.png", 5), ("cover.png", 1)];
+        let sorted = |order: PageOrder| {
+            let mut pages = pages.to_vec();
+            sort_pages(&mut pages, order, |(name, modified)| (name, *modified));
+            pages.into_iter().map(|(name, _)| name).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            sorted(PageOrder::Natural),
+            ["1.png", "2.png", "10.png", "cover.png"]
+        );
+        assert_eq!(
+            sorted(PageOrder::Reverse),
+            ["cover.png", "10.png", "2.png", "1.png"]
+        );
+
+        //Pages modified at the same time keep their natural order
+        assert_eq!(
+            sorted(PageOrder::Modified),
+            ["cover.png", "2.png", "10.png", "1.png"]
+        );
+
+        //The order chosen for a document survives being stored, unknown values fall back to names
+        for order in [PageOrder::Natural, PageOrder::Reverse, PageOrder::Modified] {
+            assert_eq!(PageOrder::from_i64(order.to_i64()), order);
+        }
+        assert_eq!(PageOrder::from_i64(7), PageOrder::Natural);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+};
+
+use crate::{
+    chunkprovider::metaprovider::MetaProvider,
+    database::title_from_path,
+    processing::{is_page_file, make_thumbnail},
+    structs::{ChunkCache, ScannedDocument},
+    traits::IChunkProvider,
+};
+
+enum ScanMessage {
+    Found(ScannedDocument),
+    Finished,
+}
+
+//Walks the library roots on its own thread, looking for documents
+pub struct LibraryScanner {
+    rx: Receiver<ScanMessage>,
+    roots: Vec<String>,
+    found: Vec<ScannedDocument>,
+    finished: bool,
+}
+
+impl LibraryScanner {
+    //Thumbnails are only generated for documents not in with_thumbnail
+    pub fn spawn(
+        roots: Vec<String>,
+        with_thumbnail: HashSet<String>,
+        thumbnail_size: (i32, i32),
+    ) -> Self {
+        let (tx, rx) = channel::<ScanMessage>();
+        let thread_roots = roots.clone();
+
+        thread::spawn(move || {
+            //Used to decode the first page of every new document
+            let provider = MetaProvider::new();
+
+            for root in thread_roots {
+                let mut documents = Vec::new();
+                find_documents(Path::new(&root), &provider, &mut documents);
+
+                for path in documents {
+                    let (thumbnail, fingerprint) = if with_thumbnail.contains(&path) {
+                        (None, None)
+                    } else {
+                        first_page_thumbnail(&provider, &path, thumbnail_size)
+                    };
+
+                    let document = ScannedDocument {
+                        title: title_from_path(&path),
+                        path,
+                        thumbnail,
+                        fingerprint,
+                    };
+
+                    if tx.send(ScanMessage::Found(document)).is_err() {
+                        return;
+                    }
+                }
+            }
+
+            tx.send(ScanMessage::Finished).ok();
+        });
+
+        Self {
+            rx,
+            roots,
+            found: Vec::new(),
+            finished: false,
+        }
+    }
+
+    //Collect the documents found so far, returns true once the scan is over
+    pub fn poll(&mut self) -> bool {
+        loop {
+            match self.rx.try_recv() {
+                Ok(ScanMessage::Found(document)) => self.found.push(document),
+                Ok(ScanMessage::Finished) | Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        self.finished
+    }
+
+    pub fn found_count(&self) -> usize {
+        self.found.len()
+    }
+
+    //The scanned roots and every document found in them
+    pub fn into_results(self) -> (Vec<String>, Vec<ScannedDocument>) {
+        (self.roots, self.found)
+    }
+}
+
+//Recursively collect documents: archives and folders holding pages
+fn find_documents(dir: &Path, provider: &MetaProvider, documents: &mut Vec<String>) {
+    let entries = match dir.read_dir() {
+        Ok(it) => it,
+        Err(error) => {
+            log::error!("Error scanning '{}': {error}", dir.display());
+            return;
+        }
+    };
+
+    let mut has_pages = false;
+    let mut subdirs = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let path_string = path.to_string_lossy().to_string();
+
+        //Skip hidden files and folders
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            subdirs.push(path);
+        } else if is_page_file(&path_string) {
+            has_pages = true;
+        } else if provider.can_open(&path_string) {
+            documents.push(path_string);
+        }
+    }
+
+    if has_pages {
+        documents.push(dir.to_string_lossy().to_string());
+    }
+
+    for subdir in subdirs {
+        find_documents(&subdir, provider, documents);
+    }
+}
+
+//Thumbnail of the first page and the document's fingerprint, read straight from its pages
+fn first_page_thumbnail(
+    provider: &MetaProvider,
+    path: &str,
+    (width, height): (i32, i32),
+) -> (Option<Vec<u8>>, Option<String>) {
+    let mut source = match provider.open_pages(path) {
+        Ok(it) if it.page_count() > 0 => it,
+        Ok(_) => return (None, None),
+        Err(error) => {
+            log::error!("Error opening '{path}' for its thumbnail: {error}");
+            return (None, None);
+        }
+    };
+
+    let fingerprint = ChunkCache {
+        chunks: Vec::new(),
+        pages: (0..source.page_count())
+            .map(|index| source.page_record(index))
+            .collect(),
+    }
+    .fingerprint();
+
+    let thumbnail = source
+        .load_page(0)
+        .and_then(|image| make_thumbnail(&image, width, height));
+
+    (thumbnail, Some(fingerprint))
+}
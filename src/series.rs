@@ -0,0 +1,237 @@
+use std::path::Path;
+
+//Words introducing a volume number, longest first so "volume" isn't taken for "vol"
+const VOLUME_MARKERS: [&str; 6] = ["volume", "vol", "tome", "book", "v", "#"];
+
+//Where a document seems to belong, guessed from its path
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeGuess {
+    //Series name as shown in the library
+    pub series: String,
+    //Identifies the series, the same for names differing only in case or punctuation
+    pub key: String,
+    pub volume: f64,
+}
+
+//Guess the series and volume of a document from its name, or from its folder's name
+//when the document is only named after its number ("Berserk/Vol 01.cbz")
+pub fn guess_volume(path: &str) -> Option<VolumeGuess> {
+    let path = Path::new(path);
+
+    //Folder documents may have dots in their names, only strip extensions from files
+    let name = if path.is_dir() {
+        path.file_name()
+    } else {
+        path.file_stem()
+    }?
+    .to_string_lossy()
+    .to_string();
+
+    let (series, volume) = split_volume(&strip_brackets(&name))?;
+
+    let series = if series.is_empty() {
+        strip_brackets(&path.parent()?.file_name()?.to_string_lossy())
+            .trim()
+            .to_string()
+    } else {
+        series
+    };
+
+    let key = series_key(&series);
+    if key.is_empty() {
+        return None;
+    }
+
+    Some(VolumeGuess {
+        series,
+        key,
+        volume,
+    })
+}
+
+//Lowercase alphanumeric words separated by single spaces
+pub fn series_key(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+//Remove "[Group]", "(2004)" and "{...}" tags, their numbers aren't volumes
+fn strip_brackets(name: &str) -> String {
+    let mut result = String::new();
+    let mut depth = 0;
+
+    for c in name.chars() {
+        match c {
+            '[' | '(' | '{' => depth += 1,
+            ']' | ')' | '}' => depth = (depth - 1).max(0),
+            _ if depth == 0 => result.push(c),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+//Split a name into the text before its volume number and the number itself
+fn split_volume(name: &str) -> Option<(String, f64)> {
+    //ASCII only, so offsets stay valid in the original name
+    let lowercase = name.to_ascii_lowercase();
+    let chars: Vec<(usize, char)> = lowercase.char_indices().collect();
+
+    //A marker at the start of a word, followed by a number: "Vol.02", "v2", "#2"
+    for (position, &(offset, _)) in chars.iter().enumerate() {
+        let word_start = position == 0 || !chars[position - 1].1.is_alphanumeric();
+        if !word_start {
+            continue;
+        }
+
+        for marker in VOLUME_MARKERS {
+            let rest = match lowercase[offset..].strip_prefix(marker) {
+                Some(it) => it.trim_start_matches(['.', ' ', '_']),
+                None => continue,
+            };
+
+            if let Some(volume) = leading_number(rest) {
+                return Some((trim_separators(&name[..offset]), volume));
+            }
+        }
+    }
+
+    //Otherwise a number closing the name: "Akira 03"
+    let trimmed = name.trim_end();
+    let start = trimmed
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .len();
+    let number = trimmed[start..].trim_start_matches('.');
+
+    if number.is_empty() {
+        return None;
+    }
+
+    let series = &trimmed[..trimmed.len() - number.len()];
+    let separated = series.chars().last().is_none_or(|c| !c.is_alphanumeric());
+
+    if !separated {
+        return None;
+    }
+
+    Some((trim_separators(series), number.parse().ok()?))
+}
+
+//Number at the start of the text, with an optional fraction ("5.5" for specials)
+fn leading_number(text: &str) -> Option<f64> {
+    let integer_length = text.chars().take_while(|c| c.is_ascii_digit()).count();
+    if integer_length == 0 {
+        return None;
+    }
+
+    let fraction_length = match text[integer_length..].strip_prefix('.') {
+        Some(rest) => match rest.chars().take_while(|c| c.is_ascii_digit()).count() {
+            0 => 0,
+            length => length + 1,
+        },
+        None => 0,
+    };
+
+    //Digits running into letters are part of a word ("v2k")
+    let end = integer_length + fraction_length;
+    if text[end..]
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic())
+    {
+        return None;
+    }
+
+    text[..end].parse().ok()
+}
+
+fn trim_separators(text: &str) -> String {
+    text.trim_matches(|c: char| c.is_whitespace() || "-_.,:#".contains(c))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guess(path: &str) -> Option<(String, f64)> {
+        guess_volume(path).map(|it| (it.series, it.volume))
+    }
+
+    fn volume(series: &str, volume: f64) -> Option<(String, f64)> {
+        Some((series.to_string(), volume))
+    }
+
+    #[test]
+    fn volume_markers() {
+        assert_eq!(guess("Berserk Vol.02.cbz"), volume("Berserk", 2.0));
+        assert_eq!(guess("Berserk Volume 2.cbz"), volume("Berserk", 2.0));
+        assert_eq!(guess("Berserk v2.cbz"), volume("Berserk", 2.0));
+        assert_eq!(guess("Berserk #2.cbz"), volume("Berserk", 2.0));
+        assert_eq!(guess("Berserk - Tome_3.cbz"), volume("Berserk", 3.0));
+    }
+
+    #[test]
+    fn trailing_number() {
+        assert_eq!(guess("Akira 03.cbz"), volume("Akira", 3.0));
+        assert_eq!(guess("Akira_03.cbz"), volume("Akira", 3.0));
+        //Digits glued to a word aren't a volume
+        assert_eq!(guess("Akira03.cbz"), None);
+    }
+
+    #[test]
+    fn specials() {
+        assert_eq!(guess("One Piece 5.5.cbz"), volume("One Piece", 5.5));
+        assert_eq!(guess("One Piece Vol.5.5.cbz"), volume("One Piece", 5.5));
+    }
+
+    #[test]
+    fn bracket_tags() {
+        assert_eq!(guess("[Group] Name (2004) v01.cbz"), volume("Name", 1.0));
+        assert_eq!(guess("Name {Digital} 07.cbz"), volume("Name", 7.0));
+    }
+
+    #[test]
+    fn named_after_folder() {
+        assert_eq!(guess("Library/Berserk/Vol 01.cbz"), volume("Berserk", 1.0));
+        assert_eq!(
+            guess("Library/[Group] Berserk/02.cbz"),
+            volume("Berserk", 2.0)
+        );
+        //Nothing to name the series after
+        assert_eq!(guess("Vol 01.cbz"), None);
+    }
+
+    #[test]
+    fn non_ascii_names() {
+        assert_eq!(guess("Pokémon Vol 3.cbz"), volume("Pokémon", 3.0));
+        assert_eq!(guess("進撃の巨人 12.cbz"), volume("進撃の巨人", 12.0));
+        assert_eq!(
+            guess("Ūrusei Yatsura #4.cbz"),
+            volume("Ūrusei Yatsura", 4.0)
+        );
+        assert_eq!(guess("ÉÉÉ v2.cbz"), volume("ÉÉÉ", 2.0));
+    }
+
+    #[test]
+    fn numbers_in_titles() {
+        assert_eq!(guess("20th Century Boys.cbz"), None);
+        assert_eq!(
+            guess("20th Century Boys 05.cbz"),
+            volume("20th Century Boys", 5.0)
+        );
+    }
+
+    #[test]
+    fn keys_ignore_case_and_punctuation() {
+        assert_eq!(series_key("Fullmetal Alchemist!"), "fullmetal alchemist");
+        assert_eq!(
+            guess_volume("Fullmetal  alchemist - 02.cbz").map(|it| it.key),
+            Some("fullmetal alchemist".to_string())
+        );
+    }
+}
@@ -8,17 +8,557 @@ pub struct Chunk {
     pub texture_index: usize,
 }
 
-#[allow(dead_code)]
+//Identifies the file a page was decoded from, to notice when it changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageRecord {
+    //File or entry name, relative to the document
+    pub name: String,
+    pub size: u64,
+    //Modification time, only compared for equality (0 if unknown)
+    pub modified: u64,
+    //Turn applied before the page was segmented
+    pub rotation: PageRotation,
+}
+
+//A marked chunk, found again by page and position since chunk indices change on re-segmentation
+#[derive(Debug, Clone)]
+pub struct Bookmark {
+    pub texture_index: usize,
+    //Chunk rect when the bookmark was added
+    pub rect: Rectangle,
+    //Empty if there is no note
+    pub note: String,
+    pub created: u64,
+}
+
+//Everything remembered about a document, as carried between machines by a progress file
+#[derive(Debug, Clone)]
+pub struct DocumentRecord {
+    pub metadata: ComicMetadata,
+    //Path inside the library folder holding the document, separated by '/'
+    pub library_path: Option<String>,
+    //ChunkCache::fingerprint of the document, if it was opened since fingerprints exist
+    pub fingerprint: Option<String>,
+    pub tags: Vec<String>,
+    //Names of the collections the document is in
+    pub collections: Vec<String>,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+//What importing a progress file did
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    //Documents whose progress was replaced by a more recent one
+    pub updated: usize,
+    //Documents read more recently here, only tags, collections and bookmarks were added
+    pub merged: usize,
+    //Documents not found in this library
+    pub unmatched: usize,
+}
+
+//Chunks detected for a document, along with the pages they were detected on
+#[derive(Debug, Clone, Default)]
+pub struct ChunkCache {
+    pub chunks: Vec<Chunk>,
+    //Indexed by the chunks' texture_index
+    pub pages: Vec<PageRecord>,
+}
+
+impl ChunkCache {
+    //Stable hash (FNV-1a) of the sorted page names and sizes, the same wherever the document is moved
+    pub fn fingerprint(&self) -> String {
+        let mut pages: Vec<&PageRecord> = self.pages.iter().collect();
+        pages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for page in pages {
+            for byte in format!("{}\0{}\n", page.name, page.size).bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+
+        format!("{hash:016x}-{}", self.pages.len())
+    }
+}
+
+//Commands sent from the viewer to the chunk worker
 #[derive(Debug)]
 pub enum ViewerCommand {
+    //Segment pages until at least how_many more chunks are produced
     MoreChunks { how_many: u32 },
 }
 
-#[allow(dead_code)]
+//Messages exchanged between the viewer and the chunk worker
 #[derive(Debug)]
 pub enum Message {
     Command(ViewerCommand),
     ChunkData(Chunk),
+    //Sent after the last chunk of a page (also for pages without chunks)
+    PageProcessed(usize),
+}
+
+//How a document's panels are meant to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadingDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    //Long strips (webtoons), panels span the whole page width
+    Vertical,
+}
+
+impl ReadingDirection {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => ReadingDirection::RightToLeft,
+            2 => ReadingDirection::Vertical,
+            _ => ReadingDirection::LeftToRight,
+        }
+    }
+
+    pub fn to_i64(self) -> i64 {
+        match self {
+            ReadingDirection::LeftToRight => 0,
+            ReadingDirection::RightToLeft => 1,
+            ReadingDirection::Vertical => 2,
+        }
+    }
+
+    //The next direction, used to cycle through them from the viewer
+    pub fn next(self) -> Self {
+        match self {
+            ReadingDirection::LeftToRight => ReadingDirection::RightToLeft,
+            ReadingDirection::RightToLeft => ReadingDirection::Vertical,
+            ReadingDirection::Vertical => ReadingDirection::LeftToRight,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReadingDirection::LeftToRight => "Left to right",
+            ReadingDirection::RightToLeft => "Right to left",
+            ReadingDirection::Vertical => "Vertical",
+        }
+    }
+}
+
+//How the pages of a document are sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageOrder {
+    //By name, comparing numbers by value (page2 before page10)
+    #[default]
+    Natural,
+    //By name, last page first
+    Reverse,
+    //By modification time, oldest first
+    Modified,
+}
+
+impl PageOrder {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => PageOrder::Reverse,
+            2 => PageOrder::Modified,
+            _ => PageOrder::Natural,
+        }
+    }
+
+    pub fn to_i64(self) -> i64 {
+        match self {
+            PageOrder::Natural => 0,
+            PageOrder::Reverse => 1,
+            PageOrder::Modified => 2,
+        }
+    }
+
+    //The next order, used to cycle through them from the viewer
+    pub fn next(self) -> Self {
+        match self {
+            PageOrder::Natural => PageOrder::Reverse,
+            PageOrder::Reverse => PageOrder::Modified,
+            PageOrder::Modified => PageOrder::Natural,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PageOrder::Natural => "By name",
+            PageOrder::Reverse => "By name, reversed",
+            PageOrder::Modified => "By modification time",
+        }
+    }
+}
+
+//Turn applied to a page that was scanned sideways or upside down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageRotation {
+    #[default]
+    None,
+    Clockwise,
+    Half,
+    CounterClockwise,
+}
+
+impl PageRotation {
+    //Stored as clockwise quarter turns
+    pub fn from_i64(value: i64) -> Self {
+        match value.rem_euclid(4) {
+            1 => PageRotation::Clockwise,
+            2 => PageRotation::Half,
+            3 => PageRotation::CounterClockwise,
+            _ => PageRotation::None,
+        }
+    }
+
+    pub fn to_i64(self) -> i64 {
+        match self {
+            PageRotation::None => 0,
+            PageRotation::Clockwise => 1,
+            PageRotation::Half => 2,
+            PageRotation::CounterClockwise => 3,
+        }
+    }
+
+    //This rotation turned further by a number of clockwise quarter turns
+    pub fn turned(self, quarter_turns: i64) -> Self {
+        Self::from_i64(self.to_i64() + quarter_turns)
+    }
+
+    //Rotate a decoded page in place
+    pub fn apply(self, image: &mut Image) {
+        match self {
+            PageRotation::None => {}
+            PageRotation::Clockwise => image.rotate_cw(),
+            PageRotation::Half => {
+                image.flip_vertical();
+                image.flip_horizontal();
+            }
+            PageRotation::CounterClockwise => image.rotate_ccw(),
+        }
+    }
+}
+
+//Whether a document is read one detected chunk at a time or one whole page at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewMode {
+    #[default]
+    Chunk,
+    //Fallback for pages the detector splits badly
+    Page,
+    //Two facing pages side by side, like a printed book
+    Spread,
+    //Every page stacked in one long strip, for webtoons
+    Continuous,
+}
+
+impl ViewMode {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => ViewMode::Page,
+            2 => ViewMode::Spread,
+            3 => ViewMode::Continuous,
+            _ => ViewMode::Chunk,
+        }
+    }
+
+    pub fn to_i64(self) -> i64 {
+        match self {
+            ViewMode::Chunk => 0,
+            ViewMode::Page => 1,
+            ViewMode::Spread => 2,
+            ViewMode::Continuous => 3,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewMode::Chunk => "Chunks",
+            ViewMode::Page => "Pages",
+            ViewMode::Spread => "Two pages",
+            ViewMode::Continuous => "Continuous",
+        }
+    }
+}
+
+//How a whole page is scaled in page mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageFit {
+    //The whole page is visible
+    #[default]
+    Page,
+    //Fills the width, scrolled vertically
+    Width,
+    //Fills the height, scrolled horizontally
+    Height,
+}
+
+impl PageFit {
+    pub fn from_i64(value: i64) -> Self {
+        match value {
+            1 => PageFit::Width,
+            2 => PageFit::Height,
+            _ => PageFit::Page,
+        }
+    }
+
+    pub fn to_i64(self) -> i64 {
+        match self {
+            PageFit::Page => 0,
+            PageFit::Width => 1,
+            PageFit::Height => 2,
+        }
+    }
+
+    //The next fit, used to cycle through them from the viewer
+    pub fn next(self) -> Self {
+        match self {
+            PageFit::Page => PageFit::Width,
+            PageFit::Width => PageFit::Height,
+            PageFit::Height => PageFit::Page,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PageFit::Page => "Fit page",
+            PageFit::Width => "Fit width",
+            PageFit::Height => "Fit height",
+        }
+    }
+}
+
+//Tunables for the chunk detector, set globally or per document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectionParams {
+    //How many consecutive gutter strips count as a chunk separator
+    pub gutter_strip_threshold: usize,
+    //Minimal height a chunk must have to be recognized as such
+    pub min_chunk_height: usize,
+    //Minimal width a chunk must have to be recognized as such
+    pub min_chunk_width: usize,
+    //Maximal difference per channel for a pixel to be taken as gutter
+    pub gutter_color_tolerance: u8,
+    //Percentage of noisy pixels tolerated in a gutter strip
+    pub max_noise_percent: usize,
+}
+
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            gutter_strip_threshold: 5,
+            min_chunk_height: 6,
+            min_chunk_width: 24,
+            gutter_color_tolerance: 40,
+            max_noise_percent: 2,
+        }
+    }
+}
+
+impl DetectionParams {
+    //Serialize as "key=value" pairs, the format used to store them in the database
+    pub fn to_config_string(&self) -> String {
+        format!(
+            "gutter_strip_threshold={},min_chunk_height={},min_chunk_width={},gutter_color_tolerance={},max_noise_percent={}",
+            self.gutter_strip_threshold,
+            self.min_chunk_height,
+            self.min_chunk_width,
+            self.gutter_color_tolerance,
+            self.max_noise_percent
+        )
+    }
+
+    //Parse the output of to_config_string, missing or invalid values keep their defaults
+    pub fn from_config_string(config: &str) -> Self {
+        let mut params = Self::default();
+
+        for pair in config.split(',') {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key {
+                "gutter_strip_threshold" => {
+                    params.gutter_strip_threshold =
+                        value.parse().unwrap_or(params.gutter_strip_threshold)
+                }
+                "min_chunk_height" => {
+                    params.min_chunk_height = value.parse().unwrap_or(params.min_chunk_height)
+                }
+                "min_chunk_width" => {
+                    params.min_chunk_width = value.parse().unwrap_or(params.min_chunk_width)
+                }
+                "gutter_color_tolerance" => {
+                    params.gutter_color_tolerance =
+                        value.parse().unwrap_or(params.gutter_color_tolerance)
+                }
+                "max_noise_percent" => {
+                    params.max_noise_percent = value.parse().unwrap_or(params.max_noise_percent)
+                }
+                _ => log::warn!("Unknown detection parameter '{key}'"),
+            }
+        }
+
+        params
+    }
+
+    //Stable hash (FNV-1a) identifying chunks computed with these parameters
+    pub fn params_hash(&self) -> i64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        for byte in self.to_config_string().bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+
+        hash as i64
+    }
+}
+
+//Order of the documents listed in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LibrarySort {
+    //Most recently opened first
+    #[default]
+    LastOpened,
+    //Alphabetically, case-insensitive
+    Title,
+    //Most read first
+    Progress,
+}
+
+impl LibrarySort {
+    pub fn from_i32(value: i32) -> Self {
+        match value {
+            1 => LibrarySort::Title,
+            2 => LibrarySort::Progress,
+            _ => LibrarySort::LastOpened,
+        }
+    }
+
+    pub fn to_i32(self) -> i32 {
+        match self {
+            LibrarySort::LastOpened => 0,
+            LibrarySort::Title => 1,
+            LibrarySort::Progress => 2,
+        }
+    }
+}
+
+//Restricts the documents listed by the library and lobby
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LibraryFilter {
+    #[default]
+    All,
+    Favourites,
+    //Documents with this tag
+    Tag(String),
+    //Documents in the collection with this id
+    Collection(i64),
+}
+
+//A named group of documents put together by the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+}
+
+//A page of library documents matching a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryQuery {
+    //Substring looked up in titles and paths, empty to list everything
+    pub search: String,
+    pub sort: LibrarySort,
+    //How many matching documents to skip
+    pub offset: usize,
+    //Maximal amount of documents returned
+    pub limit: usize,
+    //Leave out documents imported by a library scan but never opened
+    pub opened_only: bool,
+    //Return a single entry for the volumes of each series
+    pub group_series: bool,
+    //Only list the volumes of this series, by volume number
+    pub series: Option<i64>,
+    pub filter: LibraryFilter,
+}
+
+impl Default for LibraryQuery {
+    fn default() -> Self {
+        Self {
+            search: String::new(),
+            sort: LibrarySort::default(),
+            offset: 0,
+            limit: 50,
+            opened_only: false,
+            group_series: false,
+            series: None,
+            filter: LibraryFilter::default(),
+        }
+    }
+}
+
+//Documents that are volumes of the same work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Series {
+    pub id: i64,
+    pub name: String,
+    //How many of its volumes matched the query
+    pub volume_count: usize,
+}
+
+//A card of the library
+#[derive(Debug, Clone)]
+pub struct LibraryEntry {
+    //The document itself, or for a series the volume read last
+    pub document: ComicMetadata,
+    //Set when the card stands for several volumes
+    pub series: Option<Series>,
+}
+
+//A document found by the library scanner
+#[derive(Debug, Clone)]
+pub struct ScannedDocument {
+    pub path: String,
+    pub title: String,
+    //Only generated for documents that don't have one yet
+    pub thumbnail: Option<Vec<u8>>,
+    //ChunkCache::fingerprint, only known for documents a thumbnail was generated for
+    pub fingerprint: Option<String>,
+}
+
+//A stretch of time a document was open, appended to the reading log when it's closed
+#[derive(Debug, Clone)]
+pub struct ReadingSession {
+    pub path: String,
+    pub started: u64,
+    pub ended: u64,
+    //Chunks moved forward through, jumps not included
+    pub chunks_advanced: usize,
+    //Distinct pages shown
+    pub pages_seen: usize,
+}
+
+//Time read and completion of a series, or of a document outside any series
+#[derive(Debug, Clone)]
+pub struct SeriesStatistics {
+    pub name: String,
+    pub seconds_read: u64,
+    //Average of its volumes, from 0 to 1
+    pub completion: f32,
+}
+
+//Everything shown by the statistics screen
+#[derive(Debug, Clone, Default)]
+pub struct ReadingStatistics {
+    //Seconds read each day as "YYYY-MM-DD", oldest first
+    pub days: Vec<(String, u64)>,
+    pub series: Vec<SeriesStatistics>,
+    pub sessions: usize,
+    pub seconds_read: u64,
+    //Average over every opened document, from 0 to 1
+    pub completion: f32,
 }
 
 //Store metadata for books, folders, etc...
@@ -28,14 +568,34 @@ pub struct ComicMetadata {
     pub last_time_opened: u64,
     //The comic's title
     pub title: String,
-    //How many chunks were found last time(Might increase)
+    //How many chunks the document has, 0 until it was segmented to the end once
     pub chunk_count: usize,
+    //How many pages the document had when it was last closed
+    pub page_count: usize,
     //The last chunk the user was watching when application closed(Should be updated often)
     pub last_seen_chunk: usize,
     //Document Path
     pub path: String,
     //Thumbnail
     pub thumbnail: Option<Vec<u8>>,
+    //How the document's panels are ordered
+    pub reading_direction: ReadingDirection,
+    //Detection parameters for this document, None to use the global ones
+    pub detection_params: Option<DetectionParams>,
+    //How the document's pages are sorted
+    pub page_order: PageOrder,
+    //Only changed through Database::set_favourite, save_metadata leaves it alone
+    pub favourite: bool,
+    //Whether the document was last read by chunks or by whole pages
+    pub view_mode: ViewMode,
+    //How whole pages are scaled in page mode
+    pub page_fit: PageFit,
+    //Whether the first page is shown alone in spreads, so the following ones pair up as printed
+    pub spread_shift: bool,
+    //The last page the user was on, used instead of last_seen_chunk by the page based modes
+    pub last_seen_page: usize,
+    //How far down last_seen_page was scrolled in continuous mode, as a fraction of its height
+    pub page_offset: f32,
 }
 
 impl Default for ComicMetadata {
@@ -44,9 +604,19 @@ impl Default for ComicMetadata {
             last_time_opened: get_time(),
             title: String::new(),
             chunk_count: 0,
+            page_count: 0,
             last_seen_chunk: 0,
             path: String::from(""),
             thumbnail: None,
+            reading_direction: ReadingDirection::default(),
+            detection_params: None,
+            page_order: PageOrder::default(),
+            favourite: false,
+            view_mode: ViewMode::default(),
+            page_fit: PageFit::default(),
+            spread_shift: false,
+            last_seen_page: 0,
+            page_offset: 0.0,
         }
     }
 }
@@ -1,16 +1,37 @@
+use std::collections::HashMap;
+
 use raylib::texture::Image;
 
-use crate::structs::Chunk;
+use crate::{
+    chunkprovider::chunkworker::PageSource,
+    structs::{Chunk, ChunkCache, DetectionParams, PageOrder, PageRotation, ReadingDirection},
+};
 
 pub trait IChunkProvider {
     fn get_chunk(&mut self, index: usize) -> Option<&Chunk>;
+    //Chunks found so far, plus one while the document is still being segmented
     fn chunk_count(&self) -> usize;
+    //Pages of the opened document, known as soon as it's opened
+    fn page_count(&self) -> usize;
     fn done_processing(&self) -> bool;
 
     fn destroy(&self);
     fn unload(&mut self);
-    fn open(&mut self, path: &str, cached_chunks: Option<Vec<Chunk>>) -> Result<(), String>;
+    //Pages of the cache that changed since it was saved are segmented again
+    fn open(&mut self, path: &str, cache: Option<ChunkCache>) -> Result<(), String>;
+    //Sorted pages of a document, without opening it or segmenting anything
+    fn open_pages(&self, path: &str) -> Result<Box<dyn PageSource>, String>;
     fn get_image(&mut self, index: usize) -> Option<&Image>;
+    //Every chunk known so far for the opened document, to be stored
+    fn chunk_cache(&self) -> Option<ChunkCache>;
+    //Panel order used for documents opened from now on
+    fn set_reading_direction(&mut self, direction: ReadingDirection);
+    //Detector tunables used for documents opened from now on
+    fn set_detection_params(&mut self, params: DetectionParams);
+    //Page sorting used for documents opened from now on
+    fn set_page_order(&mut self, order: PageOrder);
+    //Turns applied to pages by name, before they are shown or segmented, for documents opened from now on
+    fn set_page_rotations(&mut self, rotations: HashMap<String, PageRotation>);
 
     fn can_open(&self, path: &str) -> bool;
 }
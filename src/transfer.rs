@@ -0,0 +1,336 @@
+use std::path::Path;
+
+use raylib::prelude::Rectangle;
+use serde_json::{json, Value};
+
+use crate::{
+    application::get_time,
+    database::Database,
+    structs::{
+        Bookmark, ComicMetadata, DetectionParams, DocumentRecord, ImportSummary, PageFit,
+        PageOrder, ReadingDirection, ViewMode,
+    },
+};
+
+//Bumped whenever fields change meaning, newer files are refused
+const FORMAT_VERSION: u64 = 1;
+
+//Write the progress of every document to a JSON file, returns how many were written
+pub fn export_progress(db: &Database, file: &Path) -> Result<usize, String> {
+    let records = db.export_records();
+
+    let document = json!({
+        "format_version": FORMAT_VERSION,
+        "exported": get_time(),
+        "documents": records.iter().map(record_to_json).collect::<Vec<Value>>(),
+    });
+
+    let text = serde_json::to_string_pretty(&document)
+        .map_err(|error| format!("Error writing {}: {error}", file.display()))?;
+    std::fs::write(file, text + "\n")
+        .map_err(|error| format!("Error writing {}: {error}", file.display()))?;
+
+    Ok(records.len())
+}
+
+//Merge the progress stored in a JSON file into the database
+pub fn import_progress(db: &mut Database, file: &Path) -> Result<ImportSummary, String> {
+    let text = std::fs::read_to_string(file)
+        .map_err(|error| format!("Error reading {}: {error}", file.display()))?;
+    //Nesting is limited by the parser, a hostile file can't overflow the stack
+    let document: Value = serde_json::from_str(&text)
+        .map_err(|error| format!("{} isn't a progress file: {error}", file.display()))?;
+
+    match document.get("format_version").and_then(whole_number) {
+        Some(version) if version <= FORMAT_VERSION => {}
+        Some(version) => {
+            return Err(format!(
+                "{} was exported by a newer version (format {version})",
+                file.display()
+            ))
+        }
+        None => return Err(format!("{} isn't a progress file", file.display())),
+    }
+
+    let records: Vec<DocumentRecord> = document
+        .get("documents")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(record_from_json)
+        .collect();
+
+    Ok(db.import_records(&records))
+}
+
+fn record_to_json(record: &DocumentRecord) -> Value {
+    let metadata = &record.metadata;
+
+    json!({
+        "path": metadata.path,
+        "library_path": record.library_path,
+        "fingerprint": record.fingerprint,
+        "title": metadata.title,
+        "last_time_opened": metadata.last_time_opened,
+        "chunk_count": metadata.chunk_count,
+        "page_count": metadata.page_count,
+        "last_seen_chunk": metadata.last_seen_chunk,
+        "reading_direction": metadata.reading_direction.to_i64(),
+        "page_order": metadata.page_order.to_i64(),
+        "detection_params": metadata.detection_params.map(|it| it.to_config_string()),
+        "favourite": metadata.favourite,
+        "view_mode": metadata.view_mode.to_i64(),
+        "page_fit": metadata.page_fit.to_i64(),
+        "spread_shift": metadata.spread_shift,
+        "last_seen_page": metadata.last_seen_page,
+        "page_offset": metadata.page_offset,
+        "tags": record.tags,
+        "collections": record.collections,
+        "bookmarks": record
+            .bookmarks
+            .iter()
+            .map(|bookmark| {
+                json!({
+                    "texture_index": bookmark.texture_index,
+                    "x": bookmark.rect.x,
+                    "y": bookmark.rect.y,
+                    "w": bookmark.rect.width,
+                    "h": bookmark.rect.height,
+                    "note": bookmark.note,
+                    "created": bookmark.created,
+                })
+            })
+            .collect::<Vec<Value>>(),
+    })
+}
+
+//Records without a path are skipped, every other missing field gets its default
+fn record_from_json(value: &Value) -> Option<DocumentRecord> {
+    let path = value.get("path")?.as_str()?.to_string();
+    let number = |key: &str| value.get(key).and_then(Value::as_f64);
+    let count = |key: &str| value.get(key).and_then(whole_number).unwrap_or(0);
+    let strings = |key: &str| -> Vec<String> {
+        value
+            .get(key)
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|it| it.as_str().map(str::to_string))
+            .collect()
+    };
+
+    let bookmarks = value
+        .get("bookmarks")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|bookmark| {
+            let field = |key: &str| bookmark.get(key).and_then(Value::as_f64);
+
+            Some(Bookmark {
+                texture_index: whole_number(bookmark.get("texture_index")?)? as usize,
+                rect: Rectangle::new(
+                    field("x")? as f32,
+                    field("y")? as f32,
+                    field("w")? as f32,
+                    field("h")? as f32,
+                ),
+                note: bookmark
+                    .get("note")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                created: bookmark.get("created").and_then(whole_number).unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Some(DocumentRecord {
+        metadata: ComicMetadata {
+            last_time_opened: count("last_time_opened"),
+            title: value
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            chunk_count: count("chunk_count") as usize,
+            page_count: count("page_count") as usize,
+            last_seen_chunk: count("last_seen_chunk") as usize,
+            path,
+            thumbnail: None,
+            reading_direction: ReadingDirection::from_i64(
+                number("reading_direction").unwrap_or(0.0) as i64,
+            ),
+            detection_params: value
+                .get("detection_params")
+                .and_then(Value::as_str)
+                .map(DetectionParams::from_config_string),
+            page_order: PageOrder::from_i64(number("page_order").unwrap_or(0.0) as i64),
+            favourite: value
+                .get("favourite")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            view_mode: ViewMode::from_i64(number("view_mode").unwrap_or(0.0) as i64),
+            page_fit: PageFit::from_i64(number("page_fit").unwrap_or(0.0) as i64),
+            spread_shift: value
+                .get("spread_shift")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            last_seen_page: count("last_seen_page") as usize,
+            page_offset: number("page_offset").unwrap_or(0.0) as f32,
+        },
+        library_path: value
+            .get("library_path")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        fingerprint: value
+            .get("fingerprint")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        tags: strings("tags"),
+        collections: strings("collections"),
+        bookmarks,
+    })
+}
+
+//Whole, not negative numbers, also when written with a fraction ("3.0")
+fn whole_number(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| {
+        value
+            .as_f64()
+            .filter(|it| *it >= 0.0 && it.fract() == 0.0)
+            .map(|it| it as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("manga-viewer-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let record = DocumentRecord {
+            metadata: ComicMetadata {
+                path: "/comics/Ünïcode \"quoted\"\\path.cbz".to_string(),
+                title: "Line\nbreak 🐱".to_string(),
+                last_time_opened: 1700000000,
+                chunk_count: 120,
+                page_count: 30,
+                last_seen_chunk: 42,
+                reading_direction: ReadingDirection::from_i64(1),
+                detection_params: Some(DetectionParams::default()),
+                favourite: true,
+                spread_shift: true,
+                last_seen_page: 12,
+                page_offset: 0.25,
+                ..ComicMetadata::default()
+            },
+            library_path: Some("Series/Vol 01.cbz".to_string()),
+            fingerprint: None,
+            tags: vec!["tag".to_string()],
+            collections: Vec::new(),
+            bookmarks: vec![Bookmark {
+                texture_index: 3,
+                rect: Rectangle::new(1.0, 2.5, 30.0, 40.0),
+                note: "note\twith tab".to_string(),
+                created: 1700000001,
+            }],
+        };
+
+        let text = serde_json::to_string_pretty(&record_to_json(&record)).unwrap();
+        let parsed = record_from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+
+        assert_eq!(parsed.metadata.path, record.metadata.path);
+        assert_eq!(parsed.metadata.title, record.metadata.title);
+        assert_eq!(parsed.metadata.last_time_opened, 1700000000);
+        assert_eq!(parsed.metadata.chunk_count, 120);
+        assert_eq!(parsed.metadata.page_count, 30);
+        assert_eq!(parsed.metadata.last_seen_chunk, 42);
+        assert_eq!(
+            parsed.metadata.reading_direction,
+            record.metadata.reading_direction
+        );
+        assert_eq!(
+            parsed.metadata.detection_params,
+            record.metadata.detection_params
+        );
+        assert!(parsed.metadata.favourite);
+        assert!(parsed.metadata.spread_shift);
+        assert_eq!(parsed.metadata.last_seen_page, 12);
+        assert_eq!(parsed.metadata.page_offset, 0.25);
+        assert_eq!(parsed.library_path, record.library_path);
+        assert_eq!(parsed.fingerprint, None);
+        assert_eq!(parsed.tags, record.tags);
+        assert_eq!(parsed.bookmarks.len(), 1);
+        assert_eq!(parsed.bookmarks[0].texture_index, 3);
+        assert_eq!(parsed.bookmarks[0].rect.y, 2.5);
+        assert_eq!(parsed.bookmarks[0].note, "note\twith tab");
+    }
+
+    #[test]
+    fn numbers_written_with_fractions() {
+        let value = json!({"path": "a", "chunk_count": 7.0, "last_seen_chunk": 2.5});
+        let parsed = record_from_json(&value).unwrap();
+
+        assert_eq!(parsed.metadata.chunk_count, 7);
+        //Not a whole number, falls back to the default
+        assert_eq!(parsed.metadata.last_seen_chunk, 0);
+    }
+
+    #[test]
+    fn records_without_path_are_skipped() {
+        assert!(record_from_json(&json!({"title": "No path"})).is_none());
+        assert!(record_from_json(&json!([1, 2])).is_none());
+    }
+
+    #[test]
+    fn malformed_files_are_refused() {
+        let mut db = Database::new(Path::new(":memory:"));
+        let file = temp_file("malformed.json");
+
+        let deeply_nested = "[".repeat(100_000) + &"]".repeat(100_000);
+        for text in [
+            "",
+            "{\"format_version\": 1, \"documents\": [",
+            "{\"documents\": []}",
+            "{\"format_version\": 99, \"documents\": []}",
+            "\"\\ud800\"",
+            deeply_nested.as_str(),
+        ] {
+            std::fs::write(&file, text).unwrap();
+            assert!(
+                import_progress(&mut db, &file).is_err(),
+                "accepted {text:.40}"
+            );
+        }
+
+        std::fs::remove_file(&file).ok();
+    }
+
+    #[test]
+    fn export_then_import() {
+        let mut db = Database::new(Path::new(":memory:"));
+        let metadata = ComicMetadata {
+            path: "/comics/a.cbz".to_string(),
+            last_time_opened: 5,
+            last_seen_chunk: 3,
+            ..ComicMetadata::default()
+        };
+        db.save_metadata(&vec![&metadata]).unwrap();
+
+        let file = temp_file("export.json");
+        assert_eq!(export_progress(&db, &file).unwrap(), 1);
+
+        let summary = import_progress(&mut db, &file).unwrap();
+        assert_eq!(summary.unmatched, 0);
+
+        std::fs::remove_file(&file).ok();
+    }
+}
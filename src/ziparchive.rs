@@ -0,0 +1,427 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+//Record signatures
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_END_OF_CENTRAL_DIR_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+
+//Fixed record sizes
+const LOCAL_HEADER_SIZE: u64 = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const END_OF_CENTRAL_DIR_SIZE: usize = 22;
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+
+//The end of central directory record is followed by a comment of up to 64KiB
+const MAX_COMMENT_SIZE: usize = 0xFFFF;
+
+//Supported compression methods
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+//General purpose flag for encrypted entries
+const FLAG_ENCRYPTED: u16 = 1;
+
+#[derive(Debug, Clone)]
+pub struct ZipEntryInfo {
+    pub name: String,
+    //Offset of the entry's local header
+    pub offset: u64,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub method: u16,
+    //MS-DOS date and time, as (date << 16) | time so it sorts chronologically
+    pub modified: u64,
+}
+
+//Minimal ZIP reader supporting stored and deflated entries, plus ZIP64 archives
+#[derive(Debug)]
+pub struct ZipArchive {
+    file: File,
+    //Size of the archive file, entries can't extend past it
+    file_size: u64,
+    pub entries: Vec<ZipEntryInfo>,
+}
+
+impl ZipArchive {
+    pub fn new(path: &str) -> Result<Self, String> {
+        let mut file =
+            File::open(path).map_err(|error| format!("Couldn't open '{path}': {error}"))?;
+
+        let entries = read_central_directory(&mut file)?;
+        let file_size = file
+            .metadata()
+            .map_err(|error| format!("Couldn't read the size of '{path}': {error}"))?
+            .len();
+
+        Ok(Self {
+            file,
+            file_size,
+            entries,
+        })
+    }
+
+    pub fn read(&mut self, entry: &ZipEntryInfo) -> Result<Vec<u8>, String> {
+        //The local header's variable fields may differ from the central directory ones
+        let mut header = [0u8; LOCAL_HEADER_SIZE as usize];
+        read_at(&mut self.file, entry.offset, &mut header)?;
+
+        if le_u32(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(format!("Invalid local header for '{}'", entry.name));
+        }
+
+        let name_len = le_u16(&header, 26) as u64;
+        let extra_len = le_u16(&header, 28) as u64;
+        let data_offset = entry
+            .offset
+            .checked_add(LOCAL_HEADER_SIZE + name_len + extra_len);
+
+        //Sizes come from the archive itself, a corrupted one could ask for any amount of memory
+        let data_offset = match data_offset {
+            Some(offset)
+                if offset
+                    .checked_add(entry.compressed_size)
+                    .is_some_and(|end| end <= self.file_size) =>
+            {
+                offset
+            }
+            _ => return Err(format!("Entry '{}' is out of bounds", entry.name)),
+        };
+
+        let mut data = vec![0u8; entry.compressed_size as usize];
+        read_at(&mut self.file, data_offset, &mut data)?;
+
+        match entry.method {
+            METHOD_STORED => Ok(data),
+            METHOD_DEFLATE => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(&data, entry.size as usize)
+                    .map_err(|error| format!("Error inflating '{}': {error:?}", entry.name))
+            }
+            method => Err(format!(
+                "Unsupported compression method {method} for '{}'",
+                entry.name
+            )),
+        }
+    }
+}
+
+fn read_central_directory(file: &mut File) -> Result<Vec<ZipEntryInfo>, String> {
+    let file_size = file
+        .seek(SeekFrom::End(0))
+        .map_err(|error| error.to_string())?;
+
+    if file_size < END_OF_CENTRAL_DIR_SIZE as u64 {
+        return Err("File too small to be a ZIP archive".to_string());
+    }
+
+    //Read the tail of the file and scan it backwards for the end of central directory record
+    let tail_size = file_size.min((END_OF_CENTRAL_DIR_SIZE + MAX_COMMENT_SIZE) as u64);
+    let tail_offset = file_size - tail_size;
+    let mut tail = vec![0u8; tail_size as usize];
+    read_at(file, tail_offset, &mut tail)?;
+
+    let eocd_position = (0..=tail.len() - END_OF_CENTRAL_DIR_SIZE)
+        .rev()
+        .find(|&i| le_u32(&tail, i) == END_OF_CENTRAL_DIR_SIGNATURE)
+        .ok_or_else(|| "End of central directory not found".to_string())?;
+
+    let eocd = &tail[eocd_position..];
+    let mut entry_count = le_u16(eocd, 10) as u64;
+    let mut directory_size = le_u32(eocd, 12) as u64;
+    let mut directory_offset = le_u32(eocd, 16) as u64;
+
+    //ZIP64 archives store the real values in a separate record
+    let eocd_offset = tail_offset + eocd_position as u64;
+    if eocd_offset >= ZIP64_LOCATOR_SIZE {
+        let mut locator = [0u8; ZIP64_LOCATOR_SIZE as usize];
+        read_at(file, eocd_offset - ZIP64_LOCATOR_SIZE, &mut locator)?;
+
+        if le_u32(&locator, 0) == ZIP64_LOCATOR_SIGNATURE {
+            let mut record = [0u8; 56];
+            read_at(file, le_u64(&locator, 8), &mut record)?;
+
+            if le_u32(&record, 0) != ZIP64_END_OF_CENTRAL_DIR_SIGNATURE {
+                return Err("Invalid ZIP64 end of central directory".to_string());
+            }
+
+            entry_count = le_u64(&record, 32);
+            directory_size = le_u64(&record, 40);
+            directory_offset = le_u64(&record, 48);
+        }
+    }
+
+    if directory_offset
+        .checked_add(directory_size)
+        .is_none_or(|end| end > file_size)
+    {
+        return Err("Central directory is out of bounds".to_string());
+    }
+
+    let mut directory = vec![0u8; directory_size as usize];
+    read_at(file, directory_offset, &mut directory)?;
+
+    let mut entries = Vec::new();
+    let mut position = 0;
+
+    for _ in 0..entry_count {
+        if position + CENTRAL_HEADER_SIZE > directory.len()
+            || le_u32(&directory, position) != CENTRAL_HEADER_SIGNATURE
+        {
+            return Err("Corrupted central directory".to_string());
+        }
+
+        let header = &directory[position..];
+        let flags = le_u16(header, 8);
+        let method = le_u16(header, 10);
+        let modified = (le_u16(header, 14) as u64) << 16 | le_u16(header, 12) as u64;
+        let mut compressed_size = le_u32(header, 20) as u64;
+        let mut size = le_u32(header, 24) as u64;
+        let name_len = le_u16(header, 28) as usize;
+        let extra_len = le_u16(header, 30) as usize;
+        let comment_len = le_u16(header, 32) as usize;
+        let mut offset = le_u32(header, 42) as u64;
+
+        let record_len = CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+        if position + record_len > directory.len() {
+            return Err("Corrupted central directory".to_string());
+        }
+
+        let name_bytes = &header[CENTRAL_HEADER_SIZE..CENTRAL_HEADER_SIZE + name_len];
+        let name = String::from_utf8_lossy(name_bytes).to_string();
+
+        //Values that overflow 32 bits live in the ZIP64 extra field, in this order
+        let extra =
+            &header[CENTRAL_HEADER_SIZE + name_len..CENTRAL_HEADER_SIZE + name_len + extra_len];
+        let mut extra_position = 0;
+        while extra_position + 4 <= extra.len() {
+            let id = le_u16(extra, extra_position);
+            let len = le_u16(extra, extra_position + 2) as usize;
+            let mut field = extra_position + 4;
+            let field_end = (field + len).min(extra.len());
+
+            if id == 0x0001 {
+                for value in [&mut size, &mut compressed_size, &mut offset] {
+                    if *value == 0xFFFFFFFF && field + 8 <= field_end {
+                        *value = le_u64(extra, field);
+                        field += 8;
+                    }
+                }
+            }
+
+            extra_position += 4 + len;
+        }
+
+        position += record_len;
+
+        //Skip directories and entries we can't decode
+        if name.ends_with('/') || flags & FLAG_ENCRYPTED != 0 {
+            continue;
+        }
+
+        entries.push(ZipEntryInfo {
+            name,
+            offset,
+            compressed_size,
+            size,
+            method,
+            modified,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn read_at(file: &mut File, offset: u64, buffer: &mut [u8]) -> Result<(), String> {
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(buffer))
+        .map_err(|error| error.to_string())
+}
+
+fn le_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn le_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("manga-viewer-{}-{name}", std::process::id()))
+    }
+
+    //Archive with the given entries, as (name, method, stored bytes, uncompressed size)
+    fn build_archive(entries: &[(&str, u16, &[u8], usize)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut directory = Vec::new();
+
+        for (name, method, data, size) in entries {
+            let offset = archive.len() as u32;
+
+            //Local header, the checksum isn't verified
+            archive.extend(LOCAL_HEADER_SIGNATURE.to_le_bytes());
+            archive.extend([20, 0, 0, 0]);
+            archive.extend(method.to_le_bytes());
+            archive.extend([0u8; 8]);
+            archive.extend((data.len() as u32).to_le_bytes());
+            archive.extend((*size as u32).to_le_bytes());
+            archive.extend((name.len() as u16).to_le_bytes());
+            archive.extend([0, 0]);
+            archive.extend(name.as_bytes());
+            archive.extend(*data);
+
+            directory.extend(CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+            directory.extend([20, 0, 20, 0, 0, 0]);
+            directory.extend(method.to_le_bytes());
+            directory.extend([0x00, 0x60, 0x21, 0x58]);
+            directory.extend([0u8; 4]);
+            directory.extend((data.len() as u32).to_le_bytes());
+            directory.extend((*size as u32).to_le_bytes());
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0u8; 12]);
+            directory.extend(offset.to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+
+        let directory_offset = archive.len() as u32;
+        archive.extend(&directory);
+        archive.extend(end_of_central_directory(
+            entries.len() as u16,
+            directory.len() as u32,
+            directory_offset,
+        ));
+        archive
+    }
+
+    fn end_of_central_directory(count: u16, size: u32, offset: u32) -> Vec<u8> {
+        let mut record = END_OF_CENTRAL_DIR_SIGNATURE.to_le_bytes().to_vec();
+        record.extend([0u8; 4]);
+        record.extend(count.to_le_bytes());
+        record.extend(count.to_le_bytes());
+        record.extend(size.to_le_bytes());
+        record.extend(offset.to_le_bytes());
+        record.extend([0, 0]);
+        record
+    }
+
+    fn open(name: &str, bytes: &[u8]) -> Result<ZipArchive, String> {
+        let path = temp_file(name);
+        std::fs::write(&path, bytes).unwrap();
+        let archive = ZipArchive::new(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        archive
+    }
+
+    #[test]
+    fn stored_entries() {
+        let bytes = build_archive(&[
+            ("pages/", METHOD_STORED, b"", 0),
+            ("pages/001.png", METHOD_STORED, b"first page", 10),
+            ("pages/002.png", METHOD_STORED, b"second page", 11),
+        ]);
+        let mut archive = open("stored.zip", &bytes).unwrap();
+
+        //Directories aren't listed
+        let names: Vec<&str> = archive.entries.iter().map(|it| it.name.as_str()).collect();
+        assert_eq!(names, ["pages/001.png", "pages/002.png"]);
+        assert_eq!(archive.entries[0].modified, 0x5821_6000);
+
+        let entry = archive.entries[1].clone();
+        assert_eq!(archive.read(&entry).unwrap(), b"second page");
+    }
+
+    #[test]
+    fn deflated_entry() {
+        let page = b"a page that repeats, a page that repeats, a page that repeats".repeat(20);
+        let compressed = miniz_oxide::deflate::compress_to_vec(&page, 6);
+        assert!(compressed.len() < page.len());
+
+        let bytes = build_archive(&[("001.jpg", METHOD_DEFLATE, &compressed, page.len())]);
+        let mut archive = open("deflate.zip", &bytes).unwrap();
+
+        let entry = archive.entries[0].clone();
+        assert_eq!(archive.read(&entry).unwrap(), page);
+    }
+
+    #[test]
+    fn corrupted_central_directory() {
+        let bytes = build_archive(&[("001.png", METHOD_STORED, b"page", 4)]);
+        let eocd = bytes.len() - END_OF_CENTRAL_DIR_SIZE;
+        let directory_offset = le_u32(&bytes, eocd + 16) as usize;
+
+        //Cut short in the middle of the central directory
+        let mut truncated = bytes[..directory_offset + 10].to_vec();
+        truncated.extend(&bytes[eocd..]);
+        assert!(open("truncated.zip", &truncated).is_err());
+
+        //Wrong signature where an entry should start
+        let mut corrupted = bytes.clone();
+        corrupted[directory_offset] = 0;
+        assert_eq!(
+            open("corrupted.zip", &corrupted).unwrap_err(),
+            "Corrupted central directory"
+        );
+
+        assert!(open("empty.zip", b"PK").is_err());
+    }
+
+    #[test]
+    fn zip64_directory_past_the_end() {
+        let mut bytes = build_archive(&[("001.png", METHOD_STORED, b"page", 4)]);
+        bytes.truncate(bytes.len() - END_OF_CENTRAL_DIR_SIZE);
+
+        //Size and offset adding up past u64::MAX
+        let record_offset = bytes.len() as u64;
+        bytes.extend(ZIP64_END_OF_CENTRAL_DIR_SIGNATURE.to_le_bytes());
+        bytes.extend([0u8; 28]);
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(u64::MAX.to_le_bytes());
+        bytes.extend(16u64.to_le_bytes());
+
+        bytes.extend(ZIP64_LOCATOR_SIGNATURE.to_le_bytes());
+        bytes.extend([0u8; 4]);
+        bytes.extend(record_offset.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(end_of_central_directory(0xFFFF, u32::MAX, u32::MAX));
+
+        assert_eq!(
+            open("zip64.zip", &bytes).unwrap_err(),
+            "Central directory is out of bounds"
+        );
+    }
+
+    #[test]
+    fn entry_past_the_end() {
+        let bytes = build_archive(&[("001.png", METHOD_STORED, b"page", 4)]);
+        let mut archive = open("past_end.zip", &bytes).unwrap();
+
+        for compressed_size in [bytes.len() as u64, u64::MAX] {
+            let entry = ZipEntryInfo {
+                compressed_size,
+                ..archive.entries[0].clone()
+            };
+            assert_eq!(
+                archive.read(&entry).unwrap_err(),
+                "Entry '001.png' is out of bounds"
+            );
+        }
+
+        //Offsets that overflow don't get as far as the size check
+        let entry = ZipEntryInfo {
+            offset: u64::MAX - 10,
+            ..archive.entries[0].clone()
+        };
+        assert!(archive.read(&entry).is_err());
+    }
+}